@@ -0,0 +1,324 @@
+// Analytics looks back over finished orders to answer questions
+// like "how did we do yesterday" and "what do people put on their
+// pizzas". Only served orders count
+// Revenue is what customers paid : the receipt total after discounts,
+// points and free delivery, with tax and tips. Refunds come out of
+// that same total so when the till is given they come off the order
+// they were given on. Orders saved before payments were kept with
+// them count at what the pizzas and delivery cost
+
+// Prep time is how long an order took from being placed until the
+// kitchen had it ready
+
+// A rollup puts every store side by side. Each store keeps its own
+// till so refunds are looked up in the till of the store the order
+// came from
+
+use super::date::{self, Date};
+use super::menu::format_price;
+use super::order::{Order, OrderStatus};
+use super::till::Till;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+// Sales for one day or one hour of the day
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sales {
+    pub orders: u32,
+    pub revenue: u32,
+}
+
+impl Sales {
+    // Average ticket in cents
+    pub fn average(&self) -> u32 {
+        self.revenue.checked_div(self.orders).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToppingSales {
+    pub topping: String,
+    // Pizzas the topping went on
+    pub pizzas: u32,
+}
+
+// How long orders took to get ready
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrepTimes {
+    pub median: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub longest: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub totals: Sales,
+    // Already taken off the revenue
+    pub refunds: u32,
+    // Days in order and only days with sales
+    pub by_day: Vec<(Date, Sales)>,
+    // Hours of the day (UTC) in order and only hours with sales
+    pub by_hour: Vec<(u32, Sales)>,
+    // Most popular first
+    pub toppings: Vec<ToppingSales>,
+    // None when no order has a time it was ready
+    pub prep: Option<PrepTimes>,
+}
+
+impl Report {
+    // Build a report from every served order in the list
+    pub fn new<'a, I: IntoIterator<Item = &'a Order>>(orders: I) -> Report {
+        Report::build(orders, |_: &Order| 0)
+    }
+
+    // The same with refunds from the till taken off each order
+    pub fn with_refunds<'a, I: IntoIterator<Item = &'a Order>>(orders: I, till: &Till) -> Report {
+        Report::build(orders, |order: &Order| till.refunded(order.id))
+    }
+
+    fn build<'a, I, F>(orders: I, refunded: F) -> Report
+    where
+        I: IntoIterator<Item = &'a Order>,
+        F: Fn(&Order) -> u32,
+    {
+        let served: Vec<&Order> = orders.into_iter()
+            .filter(|o| o.status() == OrderStatus::Served)
+            .collect();
+
+        let mut totals = Sales::default();
+        let mut refunds = 0;
+        let mut by_day: HashMap<Date, Sales> = HashMap::new();
+        let mut by_hour: HashMap<u32, Sales> = HashMap::new();
+        let mut toppings: HashMap<String, u32> = HashMap::new();
+        let mut prep: Vec<Duration> = Vec::new();
+
+        for order in &served {
+            let refund = refunded(order).min(revenue(order));
+            refunds += refund;
+            let revenue = revenue(order) - refund;
+            let placed = order.placed_at();
+            for sales in [&mut totals,
+                          by_day.entry(Date::of(placed)).or_default(),
+                          by_hour.entry(date::hour_of(placed)).or_default()] {
+                sales.orders += 1;
+                sales.revenue += revenue;
+            }
+
+            for pizza in &order.pizzas {
+                for topping in &pizza.toppings {
+                    *toppings.entry(topping.name.clone()).or_insert(0) += 1;
+                }
+            }
+
+            if let Some(ready) = order.time_of(OrderStatus::Ready) {
+                prep.push(ready.duration_since(placed).unwrap_or_default());
+            }
+        }
+
+        let mut by_day: Vec<(Date, Sales)> = by_day.into_iter().collect();
+        by_day.sort_by_key(|(day, _)| *day);
+        let mut by_hour: Vec<(u32, Sales)> = by_hour.into_iter().collect();
+        by_hour.sort_by_key(|(hour, _)| *hour);
+
+        // Ties are listed by name so the report is always the same
+        let mut toppings: Vec<ToppingSales> = toppings.into_iter()
+            .map(|(topping, pizzas)| ToppingSales { topping, pizzas })
+            .collect();
+        toppings.sort_by(|a, b| b.pizzas.cmp(&a.pizzas).then(a.topping.cmp(&b.topping)));
+
+        prep.sort();
+        let prep = prep.last().map(|longest| PrepTimes {
+            median: percentile(&prep, 50),
+            p90: percentile(&prep, 90),
+            p95: percentile(&prep, 95),
+            longest: *longest,
+        });
+
+        Report { totals, refunds, by_day, by_hour, toppings, prep }
+    }
+
+    // The report as tables for the terminal
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            String::from("Sales report"),
+            format!("Orders served   {:>10}", self.totals.orders),
+            format!("Revenue         {:>10}", format_price(self.totals.revenue)),
+            format!("Refunded        {:>10}", format_price(self.refunds)),
+            format!("Average ticket  {:>10}", format_price(self.totals.average())),
+            String::new(),
+            format!("{:<12}{:>8}{:>12}{:>12}", "Day", "Orders", "Revenue", "Average"),
+        ];
+        for (day, sales) in &self.by_day {
+            lines.push(format!("{:<12}{:>8}{:>12}{:>12}", day.to_string(), sales.orders,
+                format_price(sales.revenue), format_price(sales.average())));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("{:<12}{:>8}{:>12}{:>12}", "Hour", "Orders", "Revenue", "Average"));
+        for (hour, sales) in &self.by_hour {
+            lines.push(format!("{:<12}{:>8}{:>12}{:>12}", date::format_time_of_day(hour * 60),
+                sales.orders, format_price(sales.revenue), format_price(sales.average())));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("{:<20}{:>8}", "Topping", "Pizzas"));
+        for topping in &self.toppings {
+            lines.push(format!("{:<20}{:>8}", topping.topping, topping.pizzas));
+        }
+
+        lines.push(String::new());
+        match &self.prep {
+            Some(prep) => {
+                lines.push(String::from("Prep time"));
+                for (label, time) in [("Median", prep.median), ("90th percentile", prep.p90),
+                                      ("95th percentile", prep.p95), ("Longest", prep.longest)] {
+                    lines.push(format!("{:<20}{:>10}", label, format_duration(time)));
+                }
+            }
+            None => lines.push(String::from("No prep times yet")),
+        }
+        lines.join("\n")
+    }
+
+    // The report as CSV so it can go in a spreadsheet. Every row is
+    // section,key,orders,value where value is cents for revenue,
+    // pizzas for toppings and milliseconds for prep times
+    pub fn to_csv(&self) -> String {
+        let mut rows = vec![String::from("section,key,orders,value")];
+        rows.push(format!("total,revenue,{},{}", self.totals.orders, self.totals.revenue));
+        rows.push(format!("total,refunds,,{}", self.refunds));
+        rows.push(format!("total,average_ticket,{},{}", self.totals.orders, self.totals.average()));
+        for (day, sales) in &self.by_day {
+            rows.push(format!("day,{},{},{}", day, sales.orders, sales.revenue));
+        }
+        for (hour, sales) in &self.by_hour {
+            rows.push(format!("hour,{},{},{}", hour, sales.orders, sales.revenue));
+        }
+        for topping in &self.toppings {
+            rows.push(format!("topping,{},,{}", csv_field(&topping.topping), topping.pizzas));
+        }
+        if let Some(prep) = &self.prep {
+            for (label, time) in [("median", prep.median), ("p90", prep.p90), ("p95", prep.p95),
+                                  ("longest", prep.longest)] {
+                rows.push(format!("prep_ms,{},,{}", label, time.as_millis()));
+            }
+        }
+        rows.join("\n") + "\n"
+    }
+
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+// What orders are listed under when they don't have a store
+pub const NO_LOCATION: &str = "(none)";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollup {
+    // Every store together
+    pub total: Report,
+    // One report for each store code in order
+    pub locations: Vec<(String, Report)>,
+}
+
+impl Rollup {
+    pub fn new<'a, I: IntoIterator<Item = &'a Order>>(orders: I) -> Rollup {
+        Rollup::with_tills(orders, &[])
+    }
+
+    // Tills are given with the code of their store. Stores without
+    // one have no refunds
+    pub fn with_tills<'a, I: IntoIterator<Item = &'a Order>>(orders: I, tills: &[(&str, &Till)])
+                                                              -> Rollup {
+        let orders: Vec<&Order> = orders.into_iter().collect();
+        let refunded = |order: &Order| {
+            let location = order.location.as_deref().unwrap_or(NO_LOCATION);
+            tills.iter()
+                .find(|(code, _)| *code == location)
+                .map_or(0, |(_, till)| till.refunded(order.id))
+        };
+
+        let mut codes: Vec<&str> = orders.iter()
+            .map(|o| o.location.as_deref().unwrap_or(NO_LOCATION))
+            .collect();
+        codes.sort();
+        codes.dedup();
+        let locations = codes.into_iter().map(|code| {
+            let here = orders.iter().copied()
+                .filter(|o| o.location.as_deref().unwrap_or(NO_LOCATION) == code);
+            (String::from(code), Report::build(here, refunded))
+        }).collect();
+
+        Rollup { total: Report::build(orders.iter().copied(), refunded), locations }
+    }
+
+    // One row for each store and one for the whole chain
+    pub fn to_text(&self) -> String {
+        let row = |name: &str, report: &Report| {
+            let top = report.toppings.first().map_or("", |t| t.topping.as_str());
+            let median = report.prep.map_or(String::from("-"), |p| format_duration(p.median));
+            format!("{:<12}{:>8}{:>12}{:>10}{:>10}{:>10}  {}", name, report.totals.orders,
+                format_price(report.totals.revenue), format_price(report.refunds),
+                format_price(report.totals.average()), median, top).trim_end().to_string()
+        };
+        let mut lines = vec![
+            String::from("Sales by location"),
+            format!("{:<12}{:>8}{:>12}{:>10}{:>10}{:>10}  {}", "Location", "Orders", "Revenue",
+                "Refunded", "Average", "Prep", "Top topping"),
+        ];
+        for (code, report) in &self.locations {
+            lines.push(row(code, report));
+        }
+        lines.push(row("All", &self.total));
+        lines.join("\n")
+    }
+
+    // location,orders,revenue,refunds,average_ticket with cents for
+    // money and a last row for all of them
+    pub fn to_csv(&self) -> String {
+        let mut rows = vec![String::from("location,orders,revenue,refunds,average_ticket")];
+        let all = (String::from("all"), self.total.clone());
+        for (code, report) in self.locations.iter().chain(std::iter::once(&all)) {
+            rows.push(format!("{},{},{},{},{}", csv_field(code), report.totals.orders,
+                report.totals.revenue, report.refunds, report.totals.average()));
+        }
+        rows.join("\n") + "\n"
+    }
+}
+
+// What an order brought in
+fn revenue(order: &Order) -> u32 {
+    order.paid.unwrap_or_else(|| order.total() + order.delivery.as_ref().map_or(0, |d| d.fee))
+}
+
+// The nearest rank percentile of durations that are already sorted
+pub fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+// 850ms, 12.4s or 3m 05s
+pub fn format_duration(time: Duration) -> String {
+    let ms = time.as_millis();
+    if ms < 1_000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", time.as_secs_f64())
+    } else {
+        format!("{}m {:02}s", time.as_secs() / 60, time.as_secs() % 60)
+    }
+}
+
+// Names with commas or quotes are quoted
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
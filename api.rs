@@ -0,0 +1,662 @@
+// A small HTTP server so other programs can order pizza by sending
+// JSON instead of linking to our Rust code
+// HTTP is plain text sent over a TCP connection. The first line says
+// what is wanted (GET /menu HTTP/1.1), then come headers, a blank
+// line and an optional body
+
+// Endpoints
+// GET  /menu               Everything we sell with prices in cents
+// GET  /menu/calories/{n}  What keeps a pizza slice at most n calories
+// POST /orders             Place an order (see create_order for the body)
+// GET  /orders/{id}        How far along an order is
+// POST /orders/{id}/cancel Cancel an order that hasn't left the kitchen
+// GET  /deliveries/{id}    Where a delivery is and who is driving it
+// POST /deliveries/{id}/delivered The driver dropped it off
+// POST /loyalty            Join the loyalty program ({"customer": "Sally"})
+// GET  /loyalty/{customer} Points, tier and every ledger entry
+// POST /orders/{id}/review Rate a served order ({"stars": 5, "comment": "Great crust"})
+// GET  /reviews            Average ratings for every item with trends
+
+use super::date::{self, Date};
+use super::delivery::{Address, DeliveryError, Dispatch, ZoneTable};
+use super::dietary::{self, Allergen, Diet, DietaryNeeds};
+use super::history::OrderStore;
+use super::json::Json;
+use super::kitchen::{Kitchen, KitchenError};
+use super::loyalty::{Loyalty, LoyaltyError};
+use super::menu::{Category, Menu};
+use super::nutrition::NutritionTable;
+use super::order::{self, Order};
+use super::pizza_order::{Modifier, Pizza};
+use super::reviews::{ReviewBook, ReviewError};
+use super::staff::Role;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// Requests bigger than this are refused
+const MAX_BODY: usize = 64 * 1024;
+// A client that goes quiet for this long is hung up on so it can't
+// hold a thread forever
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// The request line and each header can't be longer than this and
+// there can't be more headers than this, so a client can't make the
+// server hold an endless request in memory
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+// Connections handled at once. Any more are turned away until one
+// finishes
+const MAX_CONNECTIONS: usize = 64;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Response {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            body: Json::object(vec![("error", Json::string(message))]),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+// Everything the endpoints need to do their work
+pub struct Api {
+    menu: Menu,
+    kitchen: Kitchen,
+    zones: ZoneTable,
+    // Shared with the kitchen which sends orders out when ready
+    dispatch: Arc<Mutex<Dispatch>>,
+    // Shared with the kitchen which gives points when orders are billed
+    loyalty: Arc<Mutex<Loyalty>>,
+    nutrition: NutritionTable,
+    reviews: Mutex<ReviewBook>,
+    // Orders served before the server restarted are only here
+    history: Option<Arc<Mutex<OrderStore>>>,
+    // When orders are taken, minutes since midnight in UTC. Without
+    // them the API takes orders all day
+    hours: Option<(u32, u32)>,
+}
+
+// Reviews in the last week count as recent
+const RECENT_DAYS: u32 = 7;
+
+impl Api {
+    pub fn new(menu: Menu, kitchen: Kitchen, zones: ZoneTable,
+               dispatch: Arc<Mutex<Dispatch>>, loyalty: Arc<Mutex<Loyalty>>,
+               reviews: ReviewBook) -> Api {
+        Api {
+            menu,
+            kitchen,
+            zones,
+            dispatch,
+            loyalty,
+            nutrition: NutritionTable::standard(),
+            reviews: Mutex::new(reviews),
+            history: None,
+            hours: None,
+        }
+    }
+
+    // Labels for a menu that isn't the standard one
+    pub fn with_nutrition(mut self, nutrition: NutritionTable) -> Api {
+        self.nutrition = nutrition;
+        self
+    }
+
+    // Look orders up in the saved history when the kitchen doesn't
+    // have them
+    pub fn with_history(mut self, history: Arc<Mutex<OrderStore>>) -> Api {
+        self.history = Some(history);
+        self
+    }
+
+    // Only take orders while the store is open
+    pub fn with_hours(mut self, opens: u32, closes: u32) -> Api {
+        self.hours = Some((opens, closes));
+        self
+    }
+
+    // Decide which endpoint a request is for
+    pub fn handle(&self, request: &Request) -> Response {
+        let parts: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), parts.as_slice()) {
+            ("GET", ["menu"]) => Response::ok(self.menu_json()),
+            ("GET", ["menu", "calories", limit]) => self.menu_under(limit),
+            ("POST", ["orders"]) => self.create_order(&request.body),
+            ("GET", ["orders", id]) => self.with_id(id, |id| self.order_status(id)),
+            ("POST", ["orders", id, "cancel"]) => self.with_id(id, |id| self.cancel_order(id)),
+            ("POST", ["orders", id, "review"]) =>
+                self.with_id(id, |id| self.review_order(id, &request.body)),
+            ("GET", ["reviews"]) => Response::ok(self.reviews_json()),
+            ("GET", ["deliveries", id]) => self.with_id(id, |id| self.delivery_status(id)),
+            ("POST", ["deliveries", id, "delivered"]) =>
+                self.with_id(id, |id| self.complete_delivery(id)),
+            ("POST", ["loyalty"]) => self.join_loyalty(&request.body),
+            ("GET", ["loyalty", customer]) => match decode(customer) {
+                Some(customer) => self.loyalty_account(&customer),
+                None => Response::error(400, "Customer name isn't valid"),
+            },
+            (_, ["menu", ..]) | (_, ["orders", ..]) | (_, ["deliveries", ..]) | (_, ["loyalty", ..])
+            | (_, ["reviews"]) =>
+                Response::error(405, "Method not allowed"),
+            _ => Response::error(404, "No such endpoint"),
+        }
+    }
+
+    fn with_id<F: Fn(u32) -> Response>(&self, id: &str, f: F) -> Response {
+        match id.parse() {
+            Ok(id) => f(id),
+            Err(_) => Response::error(400, "Order id must be a number"),
+        }
+    }
+
+    fn menu_json(&self) -> Json {
+        Json::Object(Category::ALL.iter().map(|category| (
+            category.name().to_lowercase(),
+            Json::Array(self.menu.items(*category).iter().map(|i| i.to_json()).collect()),
+        )).collect())
+    }
+
+    // Menu items that keep a slice of a plain pizza made with them
+    // under a calorie limit in every size, each with the calories in
+    // a slice of that pizza for the sizes it comes in
+    fn menu_under(&self, limit: &str) -> Response {
+        let limit: u32 = match limit.parse() {
+            Ok(limit) => limit,
+            Err(_) => return Response::error(400, "The calorie limit must be a whole number"),
+        };
+        let inventory = self.kitchen.inventory();
+        let inventory = inventory.lock().unwrap();
+        let under = self.nutrition.items_under(&self.menu, &inventory, limit as f64);
+        Response::ok(Json::Object(Category::ALL.iter().map(|category| (
+            category.name().to_lowercase(),
+            Json::Array(under.iter().filter(|item| item.category == *category).map(|item| {
+                let sizes = NutritionTable::sizes_for(&self.menu, item);
+                let calories = sizes.iter().filter_map(|size| {
+                    let calories = self.nutrition
+                        .pizza_slice_calories(&self.menu, &inventory, item, &size.name)?;
+                    Some((size.name.clone(), Json::from(calories.round() as i64)))
+                }).collect();
+                let mut json = item.to_json();
+                if let Json::Object(fields) = &mut json {
+                    fields.push((String::from("calories_per_slice"), Json::Object(calories)));
+                }
+                json
+            }).collect()),
+        )).collect()))
+    }
+
+    // The body looks like this. Everything but pizzas and size is optional
+    // {"customer": "Sally", "coupons": ["WELCOME5"], "allergies": ["nuts"],
+    //   "diets": ["vegetarian"], "address": "505 Main St", "points": 400,
+    //   "pizzas": [{"size": "large", "crust": "thin crust", "cheese": "mozzarella",
+    //   "toppings": ["pepperoni"], "modifiers": ["extra mozzarella"]}]}
+    // A pizza can also be {"text": "large thin crust pepperoni"}
+    // Orders with an allergen the customer listed are refused. Diet
+    // conflicts are accepted and listed under warnings
+    // Giving an address makes it a delivery order. Points are the
+    // loyalty points to spend and need a customer who is a member
+    // Outside opening hours every order is refused
+    fn create_order(&self, body: &str) -> Response {
+        if let Some((opens, closes)) = self.hours {
+            if !(opens..closes).contains(&date::minute_of_day(SystemTime::now())) {
+                return Response::error(409, &format!("We're closed, orders are taken from {} to {}",
+                    date::format_time_of_day(opens), date::format_time_of_day(closes)));
+            }
+        }
+        let json = match Json::parse(body) {
+            Ok(json) => json,
+            Err(e) => return Response::error(400, &format!("Invalid JSON : {}", e)),
+        };
+
+        let requested = match json.get("pizzas").and_then(|p| p.as_array()) {
+            Some(pizzas) if !pizzas.is_empty() => pizzas,
+            _ => return Response::error(400, "pizzas must be a list with at least 1 pizza"),
+        };
+
+        let mut pizzas = Vec::new();
+        for (i, p) in requested.iter().enumerate() {
+            match pizza_from_request(p, &self.menu) {
+                Ok(pizza) => pizzas.push(pizza),
+                Err(e) => return Response::error(400, &format!("Pizza {} : {}", i + 1, e)),
+            }
+        }
+
+        let mut order = Order::new(order::next_id(), pizzas);
+        order.customer = json.get("customer").and_then(|c| c.as_str()).map(String::from);
+        if let Some(coupons) = json.get("coupons").and_then(|c| c.as_array()) {
+            order.coupons = coupons.iter().filter_map(|c| c.as_str()).map(String::from).collect();
+        }
+        if let Some(points) = json.get("points") {
+            order.points = match points.as_u32() {
+                Some(points) => points,
+                None => return Response::error(400, "points must be a whole number"),
+            };
+        }
+        if order.points > 0 {
+            if let Err(response) = self.check_points(&order) {
+                return response;
+            }
+        }
+        order.needs = match needs_from_request(&json) {
+            Ok(needs) => needs,
+            Err(e) => return Response::error(400, &e),
+        };
+        if let Some(address) = json.get("address") {
+            let address = match address.as_str() {
+                Some(address) => address,
+                None => return Response::error(400, "address must be text"),
+            };
+            match Address::parse(address).and_then(|a| self.zones.quote(&a)) {
+                Ok(quote) => order.delivery = Some(quote),
+                Err(e @ DeliveryError::BadAddress(_)) => return Response::error(400, &e.to_string()),
+                Err(e) => return Response::error(422, &e.to_string()),
+            }
+        }
+
+        let warnings = match dietary::screen(&order) {
+            Ok(warnings) => warnings,
+            Err(e) => return Response::error(422, &e.to_string()),
+        };
+        if let Err(e) = self.kitchen.can_make(&order) {
+            return Response::error(409, &e.to_string());
+        }
+
+        // The kitchen tags the order with its store when it is
+        // submitted. Done here too so the reply says the same
+        if order.location.is_none() {
+            order.location = self.kitchen.location().map(String::from);
+        }
+        let mut body = order.to_json();
+        if let Json::Object(fields) = &mut body {
+            fields.push((String::from("warnings"),
+                Json::Array(warnings.iter().map(|w| Json::string(&w.to_string())).collect())));
+        }
+        // Hold the lock until the order is in the kitchen so it can't
+        // be ready before dispatch knows about it
+        let mut dispatch = self.dispatch.lock().unwrap();
+        let (id, delivery) = (order.id, order.delivery.is_some());
+        if delivery {
+            if let Err(e) = dispatch.book(&order) {
+                return Response::error(500, &e.to_string());
+            }
+        }
+        // A host takes API orders like they take phone ones. Nobody
+        // on shift means the manager did
+        let roster = self.kitchen.roster();
+        if let Some(roster) = &roster {
+            let _ = roster.lock().unwrap().assign(id, Role::Host, SystemTime::now());
+        }
+        match self.kitchen.submit(order) {
+            Ok(_) => Response { status: 201, body },
+            Err(e) => {
+                // The kitchen never got it so no driver should wait for it
+                if delivery {
+                    let _ = dispatch.cancel(id);
+                }
+                if let Some(roster) = &roster {
+                    roster.lock().unwrap().unassign(id, Role::Host);
+                }
+                Response::error(500, &e.to_string())
+            }
+        }
+    }
+
+    fn order_status(&self, id: u32) -> Response {
+        match self.kitchen.order(id) {
+            Some(order) => Response::ok(order.to_json()),
+            None => Response::error(404, &format!("There is no order {}", id)),
+        }
+    }
+
+    fn delivery_status(&self, id: u32) -> Response {
+        match self.dispatch.lock().unwrap().delivery(id) {
+            Some(delivery) => Response::ok(delivery.to_json()),
+            None => Response::error(404, &format!("There is no delivery for order {}", id)),
+        }
+    }
+
+    fn complete_delivery(&self, id: u32) -> Response {
+        let mut dispatch = self.dispatch.lock().unwrap();
+        match dispatch.complete(id) {
+            Ok(_) => Response::ok(dispatch.delivery(id).map_or(Json::Null, |d| d.to_json())),
+            Err(e @ DeliveryError::UnknownDelivery(_)) => Response::error(404, &e.to_string()),
+            Err(e) => Response::error(409, &e.to_string()),
+        }
+    }
+
+    // Check points when the order is placed so the customer isn't
+    // surprised when the bill comes
+    fn check_points(&self, order: &Order) -> Result<(), Response> {
+        let customer = order.customer.as_deref()
+            .ok_or(Response::error(400, "Spending points needs a customer"))?;
+        let loyalty = self.loyalty.lock().unwrap();
+        if !loyalty.is_member(customer) {
+            let e = LoyaltyError::NotAMember(String::from(customer));
+            return Err(Response::error(422, &e.to_string()));
+        }
+        let balance = loyalty.balance(customer);
+        if balance < order.points {
+            let e = LoyaltyError::NotEnoughPoints { wanted: order.points, balance };
+            return Err(Response::error(422, &e.to_string()));
+        }
+        Ok(())
+    }
+
+    fn join_loyalty(&self, body: &str) -> Response {
+        let customer = match Json::parse(body).ok()
+            .and_then(|json| json.get("customer").and_then(|c| c.as_str()).map(String::from)) {
+            Some(customer) if !customer.trim().is_empty() => customer,
+            _ => return Response::error(400, "Send the customer name like {\"customer\": \"Sally\"}"),
+        };
+        match self.loyalty.lock().unwrap().join(customer.trim()) {
+            Ok(_) => {}
+            Err(e @ LoyaltyError::AlreadyMember(_)) => return Response::error(409, &e.to_string()),
+            Err(e) => return Response::error(500, &e.to_string()),
+        }
+        let mut response = self.loyalty_account(customer.trim());
+        response.status = 201;
+        response
+    }
+
+    fn loyalty_account(&self, customer: &str) -> Response {
+        let loyalty = self.loyalty.lock().unwrap();
+        if !loyalty.is_member(customer) {
+            return Response::error(404, &LoyaltyError::NotAMember(String::from(customer)).to_string());
+        }
+        let tier = loyalty.tier(customer);
+        Response::ok(Json::object(vec![
+            ("customer", Json::string(customer)),
+            ("points", Json::from(loyalty.balance(customer))),
+            ("lifetime", Json::from(loyalty.lifetime(customer))),
+            ("tier", Json::string(tier.name())),
+            ("perks", Json::Array(tier.perks().iter().map(|p| Json::string(&p.to_string())).collect())),
+            ("ledger", Json::Array(loyalty.ledger(customer).iter().map(|e| e.to_json()).collect())),
+        ]))
+    }
+
+    // The kitchen's copy of the order says whether it was served and
+    // who worked it. Orders from before a restart come from the history
+    fn review_order(&self, id: u32, body: &str) -> Response {
+        let json = match Json::parse(body) {
+            Ok(json) => json,
+            Err(e) => return Response::error(400, &format!("Invalid JSON : {}", e)),
+        };
+        let Some(stars) = json.get("stars").and_then(|s| s.as_u32()) else {
+            return Response::error(400, "stars must be a whole number from 1 to 5");
+        };
+        let comment = match json.get("comment") {
+            None | Some(Json::Null) => "",
+            Some(comment) => match comment.as_str() {
+                Some(comment) => comment,
+                None => return Response::error(400, "comment must be text"),
+            },
+        };
+        let saved = || self.history.as_ref()?.lock().unwrap().get(id).cloned();
+        let Some(order) = self.kitchen.order(id).or_else(saved) else {
+            return Response::error(404, &format!("There is no order {}", id));
+        };
+        match self.reviews.lock().unwrap().add(&order, stars, comment) {
+            Ok(review) => Response { status: 201, body: review.to_json() },
+            Err(e @ ReviewError::BadRating(_)) => Response::error(400, &e.to_string()),
+            Err(e @ (ReviewError::NotServed(_) | ReviewError::AlreadyReviewed(_))) =>
+                Response::error(409, &e.to_string()),
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    }
+
+    fn reviews_json(&self) -> Json {
+        let reviews = self.reviews.lock().unwrap();
+        let today = Date::today();
+        Json::object(vec![
+            ("overall", reviews.overall(today, RECENT_DAYS).to_json()),
+            ("items", Json::Array(reviews.item_ratings(today, RECENT_DAYS).iter()
+                .map(|s| s.to_json()).collect())),
+            ("staff", Json::Array(reviews.staff_ratings(today, RECENT_DAYS).iter()
+                .map(|s| s.to_json()).collect())),
+        ])
+    }
+
+    fn cancel_order(&self, id: u32) -> Response {
+        match self.kitchen.cancel(id) {
+            Ok(_) => {
+                // Dine in orders have nothing booked with dispatch
+                match self.dispatch.lock().unwrap().cancel(id) {
+                    Ok(_) | Err(DeliveryError::UnknownDelivery(_)) => {}
+                    Err(e) => return Response::error(409, &e.to_string()),
+                }
+                self.order_status(id)
+            }
+            Err(e @ KitchenError::UnknownOrder(_)) => Response::error(404, &e.to_string()),
+            Err(e) => Response::error(409, &e.to_string()),
+        }
+    }
+}
+
+// Turn one pizza from a request body into a Pizza using the builder
+fn pizza_from_request(json: &Json, menu: &Menu) -> Result<Pizza, String> {
+    // Phone staff can send what the customer said instead
+    if let Some(text) = json.get("text") {
+        let text = text.as_str().ok_or("text must be text")?;
+        return Pizza::parse_from(menu, text).map_err(|e| e.to_string());
+    }
+    let size = json.get("size").and_then(|s| s.as_str()).ok_or("size is required")?;
+    let mut builder = Pizza::builder().size(size);
+    if let Some(crust) = json.get("crust").and_then(|c| c.as_str()) {
+        builder = builder.crust(crust);
+    }
+    if let Some(cheese) = json.get("cheese").and_then(|c| c.as_str()) {
+        builder = builder.cheese(cheese);
+    }
+    if let Some(toppings) = json.get("toppings") {
+        let toppings = toppings.as_array().ok_or("toppings must be a list")?;
+        for topping in toppings {
+            builder = builder.topping(topping.as_str().ok_or("toppings must be text")?);
+        }
+    }
+    if let Some(modifiers) = json.get("modifiers") {
+        let modifiers = modifiers.as_array().ok_or("modifiers must be a list")?;
+        for modifier in modifiers {
+            let text = modifier.as_str().ok_or("modifiers must be text")?;
+            builder = builder.modifier(Modifier::parse(text)
+                .ok_or(format!("{} isn't a modifier like \"extra olives\" or \"well done\"", text))?);
+        }
+    }
+    builder.build_from(menu).map_err(|e| e.to_string())
+}
+
+// Read the allergies and diets lists. Unknown names are an error
+// so a typo never hides an allergy
+fn needs_from_request(json: &Json) -> Result<DietaryNeeds, String> {
+    let names = |key: &str| -> Result<Vec<&str>, String> {
+        match json.get(key) {
+            None => Ok(Vec::new()),
+            Some(list) => list.as_array()
+                .ok_or(format!("{} must be a list", key))?
+                .iter()
+                .map(|n| n.as_str().ok_or(format!("{} must be text", key)))
+                .collect(),
+        }
+    };
+
+    let mut needs = DietaryNeeds::new();
+    for name in names("allergies")? {
+        let allergen = Allergen::from_name(name).ok_or(format!("Unknown allergy {}", name))?;
+        needs = needs.allergy(allergen);
+    }
+    for name in names("diets")? {
+        let diet = Diet::from_name(name).ok_or(format!("Unknown diet {}", name))?;
+        needs = needs.diet(diet);
+    }
+    Ok(needs)
+}
+
+// Undo the %20 style escapes browsers put in paths
+fn decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = segment.bytes();
+    while let Some(b) = rest.next() {
+        match b {
+            b'%' => {
+                let hex = [rest.next()?, rest.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Listen for connections forever. Each one gets its own thread, up
+// to MAX_CONNECTIONS at once
+pub fn serve(address: &str, api: Arc<Api>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("Taking orders at http://{}", address);
+    let busy = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Connection failed : {}", e);
+                continue;
+            }
+        };
+        // Too many clients at once are told to come back later
+        // rather than each getting a thread
+        if busy.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+            let mut stream = stream;
+            let response = Response::error(503, "The server is busy, try again soon");
+            if let Err(e) = write_response(&mut stream, &response) {
+                println!("Request failed : {}", e);
+            }
+            continue;
+        }
+        busy.fetch_add(1, Ordering::SeqCst);
+        let api = api.clone();
+        let busy = busy.clone();
+        thread::spawn(move || {
+            // Counted down even if handling the request panics
+            let _done = Done(busy);
+            if let Err(e) = handle_connection(stream, &api) {
+                println!("Request failed : {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+// Takes one off the connection count when a connection's thread ends
+struct Done(Arc<AtomicUsize>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, api: &Api) -> io::Result<()> {
+    let response = match read_request(&stream)? {
+        Ok(request) => api.handle(&request),
+        Err(refused) => refused,
+    };
+    write_response(&mut stream, &response)
+}
+
+// Reads one line of at most MAX_LINE bytes. Returns None if it is
+// longer than that
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    reader.take(MAX_LINE as u64 + 1).read_line(&mut line)?;
+    if line.len() > MAX_LINE {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+// Returns the response to send instead when the request is too big
+fn read_request(stream: &TcpStream) -> io::Result<Result<Request, Response>> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+
+    let Some(first) = read_line(&mut reader)? else {
+        return Ok(Err(Response::error(400, "The request line is too long")));
+    };
+    let mut words = first.split_whitespace();
+    let method = words.next().unwrap_or("").to_uppercase();
+    let path = words.next().unwrap_or("/").to_string();
+
+    // Headers end with an empty line. We only need the body length
+    let mut length = 0;
+    let mut headers = 0;
+    loop {
+        let Some(header) = read_line(&mut reader)? else {
+            return Ok(Err(Response::error(431, "A header is too long")));
+        };
+        if header.is_empty() || header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Ok(Err(Response::error(431, "There are too many headers")));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if length > MAX_BODY {
+        return Ok(Err(Response::error(413, "Request body is too large")));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Ok(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }))
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, response.reason(), body.len(), body)?;
+    stream.flush()
+}
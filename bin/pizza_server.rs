@@ -0,0 +1,139 @@
+// A second program that shares the restaurant module with main.rs
+// It lets other apps order pizza over HTTP
+// Run it with : cargo run --bin pizza_server
+// Try it with : curl http://127.0.0.1:7878/menu
+
+// Not everything in the restaurant module is used by this program
+#![allow(dead_code)]
+
+// Binaries in the bin directory are separate crates so they pull in
+// the restaurant module by telling Rust where its file is
+#[path = "../restaurant/mod.rs"]
+mod restaurant;
+
+use restaurant::api::{self, Api};
+use restaurant::config::RestaurantConfig;
+use restaurant::delivery::{Dispatch, ZoneTable};
+use restaurant::history::OrderStore;
+use restaurant::location::Location;
+use restaurant::loyalty::Loyalty;
+use restaurant::pizza_order::help_customer::{
+    bring_bill, send_out, serve_customer, take_payment,
+};
+use restaurant::promotions::Promotions;
+use restaurant::reviews::ReviewBook;
+use restaurant::staff::Role;
+use restaurant::tickets::{TicketFormat, TicketPrinter};
+use restaurant::till::Till;
+use std::env;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn main() {
+    // Only accept connections from this computer
+    // Pass a port to use something other than 7878
+    let port = env::args().nth(1).unwrap_or(String::from("7878"));
+    let address = format!("127.0.0.1:{}", port);
+    // Kitchen tickets go to a file until a printer's device path
+    // like /dev/usb/lp0 is given after the port
+    let printer = env::args().nth(2).unwrap_or(String::from("tickets.bin"));
+
+    let config = match RestaurantConfig::load(Path::new("restaurant.toml")) {
+        Ok(config) => config,
+        Err(e) if e.is_missing() => RestaurantConfig::standard(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let receipt_config = config.receipt.clone();
+
+    let store = match OrderStore::open("orders.jsonl") {
+        Ok(store) => Arc::new(Mutex::new(store)),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let mut promotions = match Promotions::load(Path::new("promotions.toml")) {
+        Ok(promotions) => promotions,
+        Err(e) if e.is_missing() => Promotions::new(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if let Err(e) = promotions.open_redemptions("coupons.jsonl") {
+        eprintln!("{}", e);
+        return;
+    }
+    let promotions = Mutex::new(promotions);
+
+    let loyalty = match Loyalty::open("loyalty.jsonl") {
+        Ok(loyalty) => Arc::new(Mutex::new(loyalty)),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let members = loyalty.clone();
+
+    let till = match Till::open("till.jsonl") {
+        Ok(till) => till,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let reviews = match ReviewBook::open("reviews.jsonl") {
+        Ok(reviews) => reviews,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    // The store this server takes orders for, with its own stock,
+    // staff and till
+    let here = Location::new(config).with_till(till);
+    let till = here.till.clone();
+
+    // The server runs all day so every driver on the roster takes
+    // deliveries. Cooks and drivers are noted on each order
+    let staff = here.roster.clone();
+    let mut dispatch = Dispatch::new();
+    for driver in staff.lock().unwrap().employees().iter().filter(|e| e.can_work(Role::Driver)) {
+        dispatch.add_driver(&driver.name);
+    }
+    let dispatch = Arc::new(Mutex::new(dispatch));
+    let drivers = dispatch.clone();
+
+    // Served orders are billed and saved just like the ones taken in main.rs
+    let history = store.clone();
+    let kitchen = here.open_kitchen(4, Duration::from_secs(5), move |order| {
+        if order.delivery.is_some() {
+            send_out(order, &mut drivers.lock().unwrap(), &mut staff.lock().unwrap());
+        } else {
+            serve_customer(order);
+        }
+        let receipt = bring_bill(order, &receipt_config, &mut promotions.lock().unwrap(),
+            &mut members.lock().unwrap());
+        take_payment(&mut till.lock().unwrap(), order, &receipt);
+        order.staff = staff.lock().unwrap().names(order.id);
+        if let Err(e) = store.lock().unwrap().append(order) {
+            eprintln!("{}", e);
+        }
+    });
+    kitchen.attach_printer(TicketPrinter::new(&printer, TicketFormat::EscPos));
+    kitchen.attach_dispatch(dispatch.clone());
+
+    let api = Arc::new(Api::new(here.config.menu, kitchen, ZoneTable::standard(), dispatch,
+        loyalty, reviews).with_nutrition(here.config.nutrition).with_history(history)
+        .with_hours(here.config.opens, here.config.closes));
+    if let Err(e) = api::serve(&address, api) {
+        eprintln!("Couldn't start the server : {}", e);
+    }
+}
@@ -0,0 +1,48 @@
+// Runs a simulated Friday rush to see how many cooks we need
+// Run it with : cargo run --bin simulate
+// A seed and number of cooks can be given : cargo run --bin simulate 7 4
+// The menu and tables come from restaurant.toml like the server
+
+// Not everything in the restaurant module is used by this program
+#![allow(dead_code)]
+
+#[path = "../restaurant/mod.rs"]
+mod restaurant;
+
+use restaurant::config::RestaurantConfig;
+use restaurant::simulation::{Scenario, SimReport};
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let number = |i: usize, default: u64| -> Option<u64> {
+        match args.get(i) {
+            None => Some(default),
+            Some(arg) => arg.parse().ok(),
+        }
+    };
+    let (Some(seed), Some(cooks)) = (number(1, 42), number(2, 3)) else {
+        eprintln!("Usage : simulate [seed] [cooks]");
+        return;
+    };
+
+    let config = match RestaurantConfig::load(Path::new("restaurant.toml")) {
+        Ok(config) => config,
+        Err(e) if e.is_missing() => RestaurantConfig::standard(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let rush = Scenario::friday_rush().seed(seed).tables(&config.tables);
+
+    // The same customers with more and more cooks
+    println!("{}", SimReport::summary_heading());
+    for n in 1..=5 {
+        println!("{}", rush.clone().cooks(n).run(&config.menu).summary());
+    }
+    println!();
+    println!("{}", rush.cooks(cooks as u32).run(&config.menu).to_text());
+}
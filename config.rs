@@ -0,0 +1,400 @@
+// The restaurant's settings come from restaurant.toml so the menu,
+// prices, tax, opening hours and tables can change without touching
+// the code. Every mistake in the file is reported with its line
+// Without the file the restaurant runs with the standard settings
+
+// Every menu item needs a recipe so the kitchen knows what to take
+// out of stock, and every ingredient needs nutrition for the labels
+// Items and ingredients the standard kitchen already knows can
+// leave them out. Anything else is an error when the file loads
+
+// A file looks like this, see restaurant.toml for all of it
+//   [restaurant]
+//   name = "Rusty Pizza"
+//   location = "downtown"
+//   tax = 8.25
+//   opens = "11:00"
+//   closes = "22:30"
+//
+//   [house]
+//   crust = "regular dough"
+//   cheese = "mozzarella"
+//   lunch_size = "small"
+//
+//   [[size]]
+//   name = "small"
+//   price = 8.00
+//   scale = 100      # percent of each recipe a pizza this size uses
+//   slices = 6
+//
+//   [[crust]]
+//   name = "stuffed crust"
+//   price = 2.50
+//   allergens = ["gluten", "dairy"]
+//   diets = ["vegetarian"]
+//   not_in = ["small"]
+//
+//   [[topping]]
+//   name = "anchovies"
+//   price = 2.00
+//   recipe = ["anchovies 40"]   # grams on a small pizza
+//
+//   [[ingredient]]
+//   name = "anchovies"
+//   calories = 210   # per 100g, protein, carbs and fat are grams
+//   protein = 29
+//   carbs = 0
+//   fat = 10
+//   stock = 2000     # grams on hand, 5000 if left out
+//   reorder_at = 500 # 1000 if left out
+//
+//   [[table]]
+//   number = 1
+//   seats = 2
+
+use super::date;
+use super::dietary::{Allergen, Diet};
+use super::inventory::Inventory;
+use super::menu::{Category, Menu, MenuItem};
+use super::nutrition::{Nutrition, NutritionTable};
+use super::receipt::ReceiptConfig;
+use super::reservations::ReservationBook;
+use super::seating::Floor;
+use super::toml::{self, ConfigError, Document, LoadError, Table};
+use std::path::Path;
+use std::time::SystemTime;
+
+// Headings a file can use
+const SECTIONS: [&str; 8] = ["restaurant", "house", "size", "crust", "cheese", "topping", "table",
+    "ingredient"];
+
+// Stock for an ingredient the file doesn't give an amount for
+const STOCK: u32 = 5_000;
+const REORDER_AT: u32 = 1_000;
+
+pub struct RestaurantConfig {
+    pub name: String,
+    // A short code for the store that every order it takes is
+    // tagged with like downtown or airport
+    pub location: String,
+    pub menu: Menu,
+    pub receipt: ReceiptConfig,
+    // Minutes since midnight in UTC
+    pub opens: u32,
+    pub closes: u32,
+    // Table numbers and how many seats each has
+    pub tables: Vec<(u32, u32)>,
+    // Recipes and the stock the store opens with
+    pub inventory: Inventory,
+    pub nutrition: NutritionTable,
+}
+
+impl RestaurantConfig {
+    // The settings the restaurant used before it had a file
+    pub fn standard() -> RestaurantConfig {
+        RestaurantConfig {
+            name: String::from("Rusty Pizza"),
+            location: String::from("main"),
+            menu: Menu::standard(),
+            receipt: ReceiptConfig::standard(),
+            opens: 11 * 60,
+            closes: 22 * 60 + 30,
+            tables: Floor::standard().tables().iter().map(|t| (t.number, t.capacity)).collect(),
+            inventory: Inventory::standard(),
+            nutrition: NutritionTable::standard(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<RestaurantConfig, LoadError> {
+        toml::load(path, RestaurantConfig::from_document)
+    }
+
+    pub fn from_document(doc: &Document) -> Result<RestaurantConfig, ConfigError> {
+        if let Some((name, line)) = doc.headings().into_iter().find(|(n, _)| !SECTIONS.contains(n)) {
+            return Err(ConfigError::new(line, &format!(
+                "unknown section {} (expected one of {})", name, SECTIONS.join(", "))));
+        }
+        if let Some(key) = doc.root.keys().first() {
+            return Err(ConfigError::new(doc.root.line_of(key), &format!(
+                "{} should go under a heading like [restaurant]", key)));
+        }
+
+        let restaurant = doc.table("restaurant")
+            .ok_or_else(|| ConfigError::new(1, "the [restaurant] section is missing"))?;
+        restaurant.only_keys(&["name", "location", "tax", "service_charge", "opens", "closes"])?;
+        let name = restaurant.required("name", restaurant.string("name"))?;
+        let location = restaurant.string("location")?.unwrap_or(String::from("main"));
+        if location.is_empty()
+            || !location.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+            return Err(ConfigError::new(restaurant.line_of("location"),
+                "location should be a short code of lower case letters, digits and dashes"));
+        }
+        let receipt = ReceiptConfig {
+            tax_rate: restaurant.required("tax", restaurant.percent("tax"))?,
+            service_charge: restaurant.percent("service_charge")?,
+        };
+        let opens = restaurant.required("opens", restaurant.time_of_day("opens"))?;
+        let closes = restaurant.required("closes", restaurant.time_of_day("closes"))?;
+        if closes <= opens {
+            return Err(ConfigError::new(restaurant.line_of("closes"),
+                "closes should be later than opens"));
+        }
+
+        let menu = read_menu(doc)?;
+        let tables = read_tables(doc)?;
+        let (inventory, nutrition) = read_kitchen(doc)?;
+
+        Ok(RestaurantConfig {
+            name, location, menu, receipt, opens, closes, tables, inventory, nutrition,
+        })
+    }
+
+    // A dining room with every table in the file
+    pub fn floor(&self) -> Floor {
+        let mut floor = Floor::new();
+        for (number, seats) in &self.tables {
+            // Numbers were checked to be unique when the file was read
+            floor.add_table(*number, *seats).unwrap();
+        }
+        floor
+    }
+
+    pub fn is_open(&self, at: SystemTime) -> bool {
+        (self.opens..self.closes).contains(&date::minute_of_day(at))
+    }
+
+    // A reservation book that only takes sittings we can finish
+    // before closing
+    pub fn reservation_book(&self) -> ReservationBook {
+        let mut book = ReservationBook::new();
+        let last = self.closes.saturating_sub(book.sitting_minutes).max(self.opens);
+        book.first_slot = self.opens;
+        book.last_slot = self.opens + (last - self.opens) / book.slot_minutes() * book.slot_minutes();
+        book
+    }
+}
+
+fn read_menu(doc: &Document) -> Result<Menu, ConfigError> {
+    let mut menu = Menu::new();
+    for category in Category::ALL {
+        let heading = category.name().to_lowercase();
+        let tables = doc.array(&heading);
+        if tables.is_empty() {
+            return Err(ConfigError::new(1, &format!(
+                "the menu needs at least one [[{}]]", heading)));
+        }
+        for table in tables {
+            let item = read_item(table, category)?;
+            if menu.find(category, &item.name).is_some() {
+                return Err(ConfigError::new(table.line_of("name"), &format!(
+                    "{} {} is listed twice", heading, item.name)));
+            }
+            menu.add(item);
+        }
+    }
+
+    // Crusts can name sizes they don't come in, now that every size
+    // has been read
+    for table in doc.array("crust") {
+        let crust = table.required("name", table.string("name"))?;
+        for size in table.strings("not_in")?.unwrap_or_default() {
+            if menu.find(Category::Size, &size).is_none() {
+                return Err(ConfigError::new(table.line_of("not_in"), &format!(
+                    "there is no size {} on the menu", size)));
+            }
+            menu.forbid(&crust, &size);
+        }
+    }
+
+    // Anything not in [house] keeps the usual choice, which still
+    // has to be on this menu or every plain pizza would fail
+    let mut house = menu.house().clone();
+    let section = doc.table("house");
+    if let Some(section) = section {
+        section.only_keys(&["crust", "cheese", "lunch_size"])?;
+        for (key, choice) in [("crust", &mut house.crust), ("cheese", &mut house.cheese),
+                              ("lunch_size", &mut house.lunch_size)] {
+            if let Some(name) = section.string(key)? {
+                *choice = name;
+            }
+        }
+    }
+    for (key, category, name) in [("crust", Category::Crust, &house.crust),
+                                  ("cheese", Category::Cheese, &house.cheese),
+                                  ("lunch_size", Category::Size, &house.lunch_size)] {
+        if menu.find(category, name).is_none() {
+            return Err(ConfigError::new(section.map_or(1, |s| s.line_of(key)), &format!(
+                "[house] {} is {} which isn't on the menu", key, name)));
+        }
+    }
+    menu.set_house(house);
+    Ok(menu)
+}
+
+fn read_item(table: &Table, category: Category) -> Result<MenuItem, ConfigError> {
+    let mut allowed = vec!["name", "price", "allergens", "diets"];
+    match category {
+        Category::Size => allowed.extend(["scale", "slices"]),
+        Category::Crust => allowed.extend(["not_in", "recipe"]),
+        _ => allowed.push("recipe"),
+    }
+    table.only_keys(&allowed)?;
+
+    let name = table.required("name", table.string("name"))?;
+    if name.trim().is_empty() {
+        return Err(ConfigError::new(table.line_of("name"), "name can't be empty"));
+    }
+    let price = table.required("price", table.money("price"))?;
+
+    let mut allergens = Vec::new();
+    for allergen in table.strings("allergens")?.unwrap_or_default() {
+        allergens.push(Allergen::from_name(&allergen).ok_or_else(|| {
+            let names: Vec<&str> = Allergen::ALL.iter().map(|a| a.name()).collect();
+            ConfigError::new(table.line_of("allergens"), &format!(
+                "unknown allergen {} (expected one of {})", allergen, names.join(", ")))
+        })?);
+    }
+    let mut diets = Vec::new();
+    for diet in table.strings("diets")?.unwrap_or_default() {
+        diets.push(Diet::from_name(&diet).ok_or_else(|| {
+            let names: Vec<&str> = Diet::ALL.iter().map(|d| d.name()).collect();
+            ConfigError::new(table.line_of("diets"), &format!(
+                "unknown diet {} (expected one of {})", diet, names.join(", ")))
+        })?);
+    }
+
+    Ok(MenuItem::new(&name, category, price).contains(&allergens).suits(&diets))
+}
+
+// Recipes for every item on the menu and nutrition for every
+// ingredient they use, with the standard ones filling any gaps
+fn read_kitchen(doc: &Document) -> Result<(Inventory, NutritionTable), ConfigError> {
+    let standard = Inventory::standard();
+    let standard_nutrition = NutritionTable::standard();
+    let mut inventory = Inventory::new();
+    let mut nutrition = NutritionTable::new();
+
+    let base = standard.base().to_vec();
+    inventory.set_base(&borrowed(&base));
+    // Every ingredient a recipe uses and the line that uses it
+    let mut used: Vec<(String, usize)> = base.iter().map(|(i, _)| (i.clone(), 1)).collect();
+
+    for table in doc.array("size") {
+        let name = table.required("name", table.string("name"))?;
+        let whole = |key: &str, most: i64, standard: Option<u32>, example: &str| {
+            match table.integer(key)? {
+                Some(n) if (1..=most).contains(&n) => Ok(n as u32),
+                Some(_) => Err(ConfigError::new(table.line_of(key), &format!(
+                    "{} should be a whole number from 1 to {}", key, most))),
+                None => standard.ok_or_else(|| ConfigError::new(table.line_of("name"), &format!(
+                    "size {} needs {} like {}", name, key, example))),
+            }
+        };
+        inventory.set_size_scale(&name, whole("scale", 1_000, standard.size_scale(&name),
+            "scale = 150, the percent of a small pizza's ingredients it uses")?);
+        nutrition.set_slices(&name, whole("slices", 64, standard_nutrition.slices(&name).ok(),
+            "slices = 8")?);
+    }
+
+    for category in [Category::Crust, Category::Cheese, Category::Topping] {
+        let heading = category.name().to_lowercase();
+        for table in doc.array(&heading) {
+            let name = table.required("name", table.string("name"))?;
+            let recipe = match table.strings("recipe")? {
+                Some(parts) => {
+                    let recipe: Option<Vec<(String, u32)>> = parts.iter()
+                        .map(|part| read_ingredient(part))
+                        .collect();
+                    recipe.filter(|r| !r.is_empty()).ok_or_else(|| {
+                        ConfigError::new(table.line_of("recipe"),
+                            "recipe should list ingredients and grams like [\"olives 40\"]")
+                    })?
+                }
+                None => standard.recipe_for(&name).map(|r| r.to_vec()).ok_or_else(|| {
+                    ConfigError::new(table.line_of("name"), &format!(
+                        "{} {} needs a recipe like recipe = [\"{} 40\"]", heading, name, name))
+                })?,
+            };
+            used.extend(recipe.iter().map(|(i, _)| (i.clone(), table.line_of("recipe"))));
+            inventory.set_recipe(&name, &borrowed(&recipe));
+        }
+    }
+
+    let mut stock: Vec<(String, u32, u32)> = Vec::new();
+    for table in doc.array("ingredient") {
+        table.only_keys(&["name", "calories", "protein", "carbs", "fat", "stock", "reorder_at"])?;
+        let name = table.required("name", table.string("name"))?.to_lowercase();
+        let amount = |key: &str| -> Result<f64, ConfigError> {
+            match table.required(key, table.number(key))? {
+                n if (0.0..=10_000.0).contains(&n) => Ok(n),
+                _ => Err(ConfigError::new(table.line_of(key), &format!(
+                    "{} should be the amount in 100g like 12.5", key))),
+            }
+        };
+        nutrition.set_ingredient(&name, Nutrition::new(amount("calories")?, amount("protein")?,
+            amount("carbs")?, amount("fat")?));
+        let grams = |key: &str, default: u32| -> Result<u32, ConfigError> {
+            match table.integer(key)? {
+                None => Ok(default),
+                Some(n) if (0..=1_000_000).contains(&n) => Ok(n as u32),
+                Some(_) => Err(ConfigError::new(table.line_of(key), &format!(
+                    "{} should be grams from 0 to 1000000", key))),
+            }
+        };
+        stock.push((name, grams("stock", STOCK)?, grams("reorder_at", REORDER_AT)?));
+    }
+
+    for (ingredient, line) in used {
+        if nutrition.ingredient(&ingredient).is_none() {
+            let per_100g = standard_nutrition.ingredient(&ingredient).ok_or_else(|| {
+                ConfigError::new(line, &format!("there is no nutrition for {}, add an [[ingredient]] \
+                    with its calories, protein, carbs and fat per 100g", ingredient))
+            })?;
+            nutrition.set_ingredient(&ingredient, per_100g);
+        }
+        if !stock.iter().any(|(name, _, _)| *name == ingredient) {
+            stock.push((ingredient, STOCK, REORDER_AT));
+        }
+    }
+    for (ingredient, grams, reorder_at) in stock {
+        inventory.restock(&ingredient, grams);
+        inventory.set_threshold(&ingredient, reorder_at);
+    }
+    Ok((inventory, nutrition))
+}
+
+// One part of a recipe like "olives 40"
+fn read_ingredient(text: &str) -> Option<(String, u32)> {
+    let (name, grams) = text.trim().rsplit_once(' ')?;
+    let grams: u32 = grams.parse().ok().filter(|g| *g > 0)?;
+    Some((name.trim().to_lowercase(), grams)).filter(|(name, _)| !name.is_empty())
+}
+
+fn borrowed(recipe: &[(String, u32)]) -> Vec<(&str, u32)> {
+    recipe.iter().map(|(name, grams)| (name.as_str(), *grams)).collect()
+}
+
+fn read_tables(doc: &Document) -> Result<Vec<(u32, u32)>, ConfigError> {
+    let mut tables: Vec<(u32, u32)> = Vec::new();
+    for table in doc.array("table") {
+        table.only_keys(&["number", "seats"])?;
+        let whole = |key: &str| -> Result<u32, ConfigError> {
+            match table.required(key, table.integer(key))? {
+                n if (1..=1_000).contains(&n) => Ok(n as u32),
+                _ => Err(ConfigError::new(table.line_of(key), &format!(
+                    "{} should be a whole number from 1 to 1000", key))),
+            }
+        };
+        let number = whole("number")?;
+        let seats = whole("seats")?;
+        if tables.iter().any(|(n, _)| *n == number) {
+            return Err(ConfigError::new(table.line_of("number"), &format!(
+                "table {} is listed twice", number)));
+        }
+        tables.push((number, seats));
+    }
+    if tables.is_empty() {
+        return Err(ConfigError::new(1, "the dining room needs at least one [[table]]"));
+    }
+    Ok(tables)
+}
@@ -0,0 +1,208 @@
+// Helpers for working with calendar dates without another crate
+// The standard library only knows SystemTime which counts time
+// since January 1st 1970 (the UNIX epoch) so we convert that into
+// years, months and days ourselves
+
+// All dates and hours are in UTC
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    // Returns None for dates that don't exist like February 30th
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        let date = Date { year, month, day };
+        if (1..=12).contains(&month) && day >= 1 && Date::from_days(date.days()) == date {
+            Some(date)
+        } else {
+            None
+        }
+    }
+
+    // The date a moment in time falls on
+    pub fn of(time: SystemTime) -> Date {
+        Date::from_days(seconds(time).div_euclid(SECONDS_PER_DAY))
+    }
+
+    pub fn today() -> Date {
+        Date::of(SystemTime::now())
+    }
+
+    // Reads dates written like 2024-03-15
+    pub fn parse(text: &str) -> Option<Date> {
+        let parts: Vec<&str> = text.trim().split('-').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        Date::new(parts[0].parse().ok()?, parts[1].parse().ok()?, parts[2].parse().ok()?)
+    }
+
+    // Midnight at the start of the day
+    pub fn start(&self) -> SystemTime {
+        from_seconds(self.days() * SECONDS_PER_DAY)
+    }
+
+    pub fn next(&self) -> Date {
+        Date::from_days(self.days() + 1)
+    }
+
+    pub fn previous(&self) -> Date {
+        Date::from_days(self.days() - 1)
+    }
+
+    // Days since 1970-01-01 using Howard Hinnant's days_from_civil
+    // The year is shifted to start in March so leap days fall at
+    // the very end of it
+    pub fn days(&self) -> i64 {
+        let y = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    // 1970-01-01 was a Thursday
+    pub fn day_of_week(&self) -> Day {
+        Day::ALL[(self.days() + 3).rem_euclid(7) as usize]
+    }
+
+    // The Monday a week starts on
+    pub fn monday(&self) -> Date {
+        Date::from_days(self.days() - (self.days() + 3).rem_euclid(7))
+    }
+
+    // The reverse of days()
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+}
+
+// Prints as 2024-03-15
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Day {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Day {
+    // The week starts on Monday
+    pub const ALL: [Day; 7] = [Day::Monday, Day::Tuesday, Day::Wednesday, Day::Thursday,
+        Day::Friday, Day::Saturday, Day::Sunday];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Day::Monday => "Monday",
+            Day::Tuesday => "Tuesday",
+            Day::Wednesday => "Wednesday",
+            Day::Thursday => "Thursday",
+            Day::Friday => "Friday",
+            Day::Saturday => "Saturday",
+            Day::Sunday => "Sunday",
+        }
+    }
+
+    // Takes the whole name or the first 3 letters in any case
+    pub fn from_name(name: &str) -> Option<Day> {
+        let name = name.trim();
+        Day::ALL.into_iter().find(|d| {
+            d.name().eq_ignore_ascii_case(name)
+                || (name.len() == 3 && d.name()[..3].eq_ignore_ascii_case(name))
+        })
+    }
+
+    pub fn is_weekend(&self) -> bool {
+        matches!(self, Day::Saturday | Day::Sunday)
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Whole seconds since the epoch (negative before 1970)
+pub fn seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+pub fn from_seconds(secs: i64) -> SystemTime {
+    from_millis(secs * 1_000)
+}
+
+// Milliseconds are what we store on disk so times keep their precision
+pub fn millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+pub fn from_millis(ms: i64) -> SystemTime {
+    if ms >= 0 {
+        UNIX_EPOCH + Duration::from_millis(ms as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_millis(ms.unsigned_abs())
+    }
+}
+
+// The hour of the day from 0 to 23
+pub fn hour_of(time: SystemTime) -> u32 {
+    (seconds(time).rem_euclid(SECONDS_PER_DAY) / 3_600) as u32
+}
+
+// Minutes since midnight from 0 to 1439
+pub fn minute_of_day(time: SystemTime) -> u32 {
+    (seconds(time).rem_euclid(SECONDS_PER_DAY) / 60) as u32
+}
+
+// Reads a 24 hour time like "14:30" as minutes since midnight
+pub fn parse_time_of_day(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+// Shows minutes since midnight as a time like 09:05
+pub fn format_time_of_day(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,429 @@
+// Delivery orders go to the customer's door instead of a table
+// The zone table knows how far every street we deliver to is from
+// the shop. That distance decides the zone, the fee and how long
+// the customer should expect to wait
+// Dispatch hands ready orders to free drivers and keeps track of
+// them until they are delivered
+
+use super::json::Json;
+use super::menu::format_price;
+use super::order::Order;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryError {
+    // We couldn't read a house number and street from it
+    BadAddress(String),
+    UnknownStreet(String),
+    OutsideArea { address: String, distance: u32 },
+    // The order is dine in
+    NotForDelivery(u32),
+    AlreadyBooked(u32),
+    UnknownDelivery(u32),
+    NoFreeDriver(u32),
+    // Only orders a driver has can be delivered
+    NotOnTheWay(u32),
+    // Too late to call the driver back
+    AlreadyLeft(u32),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::BadAddress(text) =>
+                write!(f, "{} isn't an address like 505 Main St", text),
+            DeliveryError::UnknownStreet(street) => write!(f, "We don't deliver to {}", street),
+            DeliveryError::OutsideArea { address, distance } =>
+                write!(f, "{} is {:.1}km away which is outside our delivery area",
+                    address, *distance as f64 / 1_000.0),
+            DeliveryError::NotForDelivery(id) => write!(f, "Order {} has no delivery address", id),
+            DeliveryError::AlreadyBooked(id) => write!(f, "Order {} is already booked", id),
+            DeliveryError::UnknownDelivery(id) => write!(f, "There is no delivery for order {}", id),
+            DeliveryError::NoFreeDriver(id) =>
+                write!(f, "No driver is free so order {} is waiting", id),
+            DeliveryError::NotOnTheWay(id) => write!(f, "Order {} hasn't left with a driver", id),
+            DeliveryError::AlreadyLeft(id) => write!(f, "Order {} has already left the shop", id),
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Address {
+    pub number: u32,
+    // The street as the customer wrote it
+    pub street: String,
+}
+
+impl Address {
+    // Reads addresses like "505 Main St". Anything after a comma
+    // (an apartment number) is for the driver and is ignored
+    pub fn parse(text: &str) -> Result<Address, DeliveryError> {
+        let bad = || DeliveryError::BadAddress(String::from(text.trim()));
+        let first_line = text.split(',').next().unwrap_or("").trim();
+        let (number, street) = first_line.split_once(' ').ok_or_else(bad)?;
+        let street = street.trim();
+        if street.is_empty() {
+            return Err(bad());
+        }
+        Ok(Address {
+            number: number.parse().map_err(|_| bad())?,
+            street: String::from(street),
+        })
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.number, self.street)
+    }
+}
+
+// Main St, main street and MAIN ST. all become "main street"
+fn street_key(street: &str) -> String {
+    let words: Vec<String> = street.split_whitespace().map(|word| {
+        let word = word.trim_end_matches('.').to_lowercase();
+        let full = match word.as_str() {
+            "st" => "street",
+            "ave" | "av" => "avenue",
+            "rd" => "road",
+            "dr" => "drive",
+            "ln" => "lane",
+            "blvd" => "boulevard",
+            other => other,
+        };
+        String::from(full)
+    }).collect();
+    words.join(" ")
+}
+
+// A stretch of street and how far it is from the shop in meters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Street {
+    pub name: String,
+    pub first: u32,
+    pub last: u32,
+    pub distance: u32,
+}
+
+// Every address up to max_distance meters away is in the zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub name: String,
+    pub max_distance: u32,
+    pub base_fee: u32,
+    // Cents added for every kilometer
+    pub per_km: u32,
+}
+
+// What it costs to deliver to an address and how long it takes
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryQuote {
+    pub address: Address,
+    pub zone: String,
+    pub distance: u32,
+    pub fee: u32,
+    pub minutes: u32,
+}
+
+impl DeliveryQuote {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("address", Json::string(&self.address.to_string())),
+            ("zone", Json::string(&self.zone)),
+            ("distance", Json::from(self.distance)),
+            ("fee", Json::from(self.fee)),
+            ("minutes", Json::from(self.minutes)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<DeliveryQuote> {
+        Some(DeliveryQuote {
+            address: Address::parse(json.get("address")?.as_str()?).ok()?,
+            zone: String::from(json.get("zone")?.as_str()?),
+            distance: json.get("distance")?.as_u32()?,
+            fee: json.get("fee")?.as_u32()?,
+            minutes: json.get("minutes")?.as_u32()?,
+        })
+    }
+}
+
+pub struct ZoneTable {
+    streets: Vec<Street>,
+    // Kept sorted so the closest zone is found first
+    zones: Vec<Zone>,
+    // How long the kitchen takes and how fast drivers cover ground
+    pub prep_minutes: u32,
+    pub minutes_per_km: u32,
+}
+
+impl ZoneTable {
+    pub fn new(prep_minutes: u32, minutes_per_km: u32) -> ZoneTable {
+        ZoneTable {
+            streets: Vec::new(),
+            zones: Vec::new(),
+            prep_minutes,
+            minutes_per_km,
+        }
+    }
+
+    // The streets around the shop
+    pub fn standard() -> ZoneTable {
+        let mut table = ZoneTable::new(20, 3);
+
+        table.add_zone("Zone A", 2_000, 199, 0);
+        table.add_zone("Zone B", 5_000, 299, 50);
+        table.add_zone("Zone C", 8_000, 399, 75);
+
+        table.add_street("Main Street", 1, 999, 800);
+        table.add_street("Main Street", 1_000, 1_999, 1_600);
+        table.add_street("Oak Avenue", 1, 499, 2_200);
+        table.add_street("Elm Street", 1, 999, 3_500);
+        table.add_street("Maple Drive", 1, 799, 4_800);
+        table.add_street("River Road", 1, 2_999, 7_000);
+        table.add_street("Hill Road", 1, 999, 11_000);
+
+        table
+    }
+
+    pub fn add_street(&mut self, name: &str, first: u32, last: u32, distance: u32) {
+        self.streets.push(Street { name: String::from(name), first, last, distance });
+    }
+
+    pub fn add_zone(&mut self, name: &str, max_distance: u32, base_fee: u32, per_km: u32) {
+        self.zones.push(Zone { name: String::from(name), max_distance, base_fee, per_km });
+        self.zones.sort_by_key(|z| z.max_distance);
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    // How far an address is from the shop in meters
+    pub fn distance(&self, address: &Address) -> Result<u32, DeliveryError> {
+        let key = street_key(&address.street);
+        let mut known = false;
+        for street in self.streets.iter().filter(|s| street_key(&s.name) == key) {
+            known = true;
+            if (street.first..=street.last).contains(&address.number) {
+                return Ok(street.distance);
+            }
+        }
+        if known {
+            Err(DeliveryError::BadAddress(address.to_string()))
+        } else {
+            Err(DeliveryError::UnknownStreet(address.street.clone()))
+        }
+    }
+
+    // Find the zone for an address and price the delivery
+    pub fn quote(&self, address: &Address) -> Result<DeliveryQuote, DeliveryError> {
+        let distance = self.distance(address)?;
+        let zone = self.zones.iter().find(|z| distance <= z.max_distance)
+            .ok_or_else(|| DeliveryError::OutsideArea { address: address.to_string(), distance })?;
+
+        // Round to the nearest cent and up to the next whole minute
+        let fee = zone.base_fee + (zone.per_km * distance + 500) / 1_000;
+        let drive = (self.minutes_per_km * distance).div_ceil(1_000);
+
+        Ok(DeliveryQuote {
+            address: address.clone(),
+            zone: zone.name.clone(),
+            distance,
+            fee,
+            minutes: self.prep_minutes + drive,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    InTheKitchen,
+    // Ready but every driver is out
+    Waiting,
+    OnTheWay,
+    Delivered,
+    // The order was cancelled before it left
+    Cancelled,
+}
+
+impl DeliveryStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeliveryStatus::InTheKitchen => "In the kitchen",
+            DeliveryStatus::Waiting => "Waiting for a driver",
+            DeliveryStatus::OnTheWay => "On the way",
+            DeliveryStatus::Delivered => "Delivered",
+            DeliveryStatus::Cancelled => "Cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub order_id: u32,
+    pub quote: DeliveryQuote,
+    pub status: DeliveryStatus,
+    pub driver: Option<String>,
+    // When we told the customer to expect it
+    pub promised: SystemTime,
+    pub left_at: Option<SystemTime>,
+    pub delivered_at: Option<SystemTime>,
+}
+
+impl Delivery {
+    // Delivered orders are on time if they arrived by the promise
+    pub fn on_time(&self) -> Option<bool> {
+        self.delivered_at.map(|at| at <= self.promised)
+    }
+
+    pub fn to_json(&self) -> Json {
+        let at = |time: Option<SystemTime>| Json::from(time.map(super::date::millis));
+        Json::object(vec![
+            ("order_id", Json::from(self.order_id)),
+            ("status", Json::string(self.status.name())),
+            ("driver", Json::from(self.driver.as_deref().map(Json::string))),
+            ("quote", self.quote.to_json()),
+            ("promised", Json::from(super::date::millis(self.promised))),
+            ("left_at", at(self.left_at)),
+            ("delivered_at", at(self.delivered_at)),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Driver {
+    pub name: String,
+    // The order they are out with
+    pub carrying: Option<u32>,
+    pub delivered: u32,
+}
+
+pub struct Dispatch {
+    drivers: Vec<Driver>,
+    // In the order they were booked so the oldest goes out first
+    deliveries: Vec<Delivery>,
+}
+
+impl Dispatch {
+    pub fn new() -> Dispatch {
+        Dispatch {
+            drivers: Vec::new(),
+            deliveries: Vec::new(),
+        }
+    }
+
+    pub fn add_driver(&mut self, name: &str) {
+        self.drivers.push(Driver {
+            name: String::from(name),
+            carrying: None,
+            delivered: 0,
+        });
+    }
+
+    pub fn drivers(&self) -> &[Driver] {
+        &self.drivers
+    }
+
+    pub fn delivery(&self, order_id: u32) -> Option<&Delivery> {
+        self.deliveries.iter().find(|d| d.order_id == order_id)
+    }
+
+    // Ready deliveries nobody has taken yet, oldest first
+    pub fn waiting(&self) -> Vec<&Delivery> {
+        self.deliveries.iter().filter(|d| d.status == DeliveryStatus::Waiting).collect()
+    }
+
+    // Start tracking a delivery order as soon as it is placed
+    pub fn book(&mut self, order: &Order) -> Result<&Delivery, DeliveryError> {
+        let quote = order.delivery.clone().ok_or(DeliveryError::NotForDelivery(order.id))?;
+        if self.delivery(order.id).is_some() {
+            return Err(DeliveryError::AlreadyBooked(order.id));
+        }
+        let promised = order.placed_at() + Duration::from_secs(quote.minutes as u64 * 60);
+        self.deliveries.push(Delivery {
+            order_id: order.id,
+            quote,
+            status: DeliveryStatus::InTheKitchen,
+            driver: None,
+            promised,
+            left_at: None,
+            delivered_at: None,
+        });
+        Ok(&self.deliveries[self.deliveries.len() - 1])
+    }
+
+    fn index_of(&self, order_id: u32) -> Result<usize, DeliveryError> {
+        self.deliveries.iter().position(|d| d.order_id == order_id)
+            .ok_or(DeliveryError::UnknownDelivery(order_id))
+    }
+
+    // Give a ready order to the first free driver. If nobody is
+    // free it keeps waiting and goes out when a driver gets back
+    pub fn assign(&mut self, order_id: u32) -> Result<String, DeliveryError> {
+        let index = self.index_of(order_id)?;
+        match self.deliveries[index].status {
+            DeliveryStatus::InTheKitchen => self.deliveries[index].status = DeliveryStatus::Waiting,
+            DeliveryStatus::Waiting => {}
+            _ => return Err(DeliveryError::AlreadyBooked(order_id)),
+        }
+        let driver = self.drivers.iter_mut().find(|d| d.carrying.is_none())
+            .ok_or(DeliveryError::NoFreeDriver(order_id))?;
+        driver.carrying = Some(order_id);
+
+        let delivery = &mut self.deliveries[index];
+        delivery.status = DeliveryStatus::OnTheWay;
+        delivery.driver = Some(driver.name.clone());
+        delivery.left_at = Some(SystemTime::now());
+        Ok(driver.name.clone())
+    }
+
+    // The order won't be made so no driver should wait for it
+    pub fn cancel(&mut self, order_id: u32) -> Result<(), DeliveryError> {
+        let index = self.index_of(order_id)?;
+        match self.deliveries[index].status {
+            DeliveryStatus::InTheKitchen | DeliveryStatus::Waiting => {
+                self.deliveries[index].status = DeliveryStatus::Cancelled;
+                Ok(())
+            }
+            DeliveryStatus::Cancelled => Ok(()),
+            _ => Err(DeliveryError::AlreadyLeft(order_id)),
+        }
+    }
+
+    // The driver is back. Anything waiting goes out with the free
+    // drivers. Returns who took which order
+    pub fn complete(&mut self, order_id: u32) -> Result<Vec<(u32, String)>, DeliveryError> {
+        let index = self.index_of(order_id)?;
+        if self.deliveries[index].status != DeliveryStatus::OnTheWay {
+            return Err(DeliveryError::NotOnTheWay(order_id));
+        }
+        let delivery = &mut self.deliveries[index];
+        delivery.status = DeliveryStatus::Delivered;
+        delivery.delivered_at = Some(SystemTime::now());
+
+        if let Some(driver) = self.drivers.iter_mut().find(|d| d.carrying == Some(order_id)) {
+            driver.carrying = None;
+            driver.delivered += 1;
+        }
+
+        let mut sent = Vec::new();
+        let waiting: Vec<u32> = self.waiting().iter().map(|d| d.order_id).collect();
+        for id in waiting {
+            match self.assign(id) {
+                Ok(driver) => sent.push((id, driver)),
+                Err(_) => break,
+            }
+        }
+        Ok(sent)
+    }
+}
+
+// One line for the kitchen display like :
+// 505 Main St (Zone A, 0.8km) $1.99 about 23 minutes
+pub fn describe(quote: &DeliveryQuote) -> String {
+    format!("{} ({}, {:.1}km) {} about {} minutes", quote.address, quote.zone,
+        quote.distance as f64 / 1_000.0, format_price(quote.fee), quote.minutes)
+}
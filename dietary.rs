@@ -0,0 +1,238 @@
+// Allergens and diets so we can tell a customer exactly which part
+// of a pizza they can't eat
+// Every menu item lists the allergens it contains and the diets it
+// is suitable for. A pizza suits a diet only if every part does
+
+use super::menu::{Category, MenuItem};
+use super::order::Order;
+use super::pizza_order::Pizza;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Allergen {
+    Gluten,
+    Dairy,
+    Nuts,
+    Soy,
+    Egg,
+}
+
+impl Allergen {
+    pub const ALL: [Allergen; 5] =
+        [Allergen::Gluten, Allergen::Dairy, Allergen::Nuts, Allergen::Soy, Allergen::Egg];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Allergen::Gluten => "gluten",
+            Allergen::Dairy => "dairy",
+            Allergen::Nuts => "nuts",
+            Allergen::Soy => "soy",
+            Allergen::Egg => "egg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Allergen> {
+        Allergen::ALL.into_iter().find(|a| a.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Diet {
+    Vegan,
+    Vegetarian,
+    GlutenFree,
+}
+
+impl Diet {
+    pub const ALL: [Diet; 3] = [Diet::Vegan, Diet::Vegetarian, Diet::GlutenFree];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Diet::Vegan => "vegan",
+            Diet::Vegetarian => "vegetarian",
+            Diet::GlutenFree => "gluten-free",
+        }
+    }
+
+    // Short labels printed next to items on the menu
+    pub fn label(&self) -> &'static str {
+        match self {
+            Diet::Vegan => "VG",
+            Diet::Vegetarian => "V",
+            Diet::GlutenFree => "GF",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Diet> {
+        let name = name.trim().replace([' ', '_'], "-");
+        Diet::ALL.into_iter().find(|d| d.name().eq_ignore_ascii_case(&name))
+    }
+}
+
+// What a customer can't or won't eat
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DietaryNeeds {
+    pub allergies: Vec<Allergen>,
+    pub diets: Vec<Diet>,
+}
+
+impl DietaryNeeds {
+    pub fn new() -> DietaryNeeds {
+        DietaryNeeds::default()
+    }
+
+    pub fn allergy(mut self, allergen: Allergen) -> DietaryNeeds {
+        self.allergies.push(allergen);
+        self
+    }
+
+    pub fn diet(mut self, diet: Diet) -> DietaryNeeds {
+        self.diets.push(diet);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allergies.is_empty() && self.diets.is_empty()
+    }
+
+    // Something like : allergic to nuts, dairy; vegan
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.allergies.is_empty() {
+            let names: Vec<&str> = self.allergies.iter().map(|a| a.name()).collect();
+            parts.push(format!("allergic to {}", names.join(", ")));
+        }
+        if !self.diets.is_empty() {
+            let names: Vec<&str> = self.diets.iter().map(|d| d.name()).collect();
+            parts.push(names.join(", "));
+        }
+        parts.join("; ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    // The item contains something the customer is allergic to
+    Contains(Allergen),
+    // The item doesn't fit a diet the customer follows
+    NotSuitable(Diet),
+}
+
+// One part of a pizza that doesn't work for the customer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub ingredient: String,
+    pub problem: Problem,
+}
+
+impl Conflict {
+    // Allergies are a safety issue so the order is refused. A diet
+    // conflict is only flagged so staff can check with the customer
+    pub fn is_rejection(&self) -> bool {
+        matches!(self.problem, Problem::Contains(_))
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.problem {
+            Problem::Contains(allergen) =>
+                write!(f, "{} contains {}", self.ingredient, allergen.name()),
+            Problem::NotSuitable(diet) =>
+                write!(f, "{} is not {}", self.ingredient, diet.name()),
+        }
+    }
+}
+
+fn item_conflicts(item: &MenuItem, needs: &DietaryNeeds) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for allergen in &needs.allergies {
+        if item.allergens.contains(allergen) {
+            conflicts.push(Conflict {
+                ingredient: item.name.clone(),
+                problem: Problem::Contains(*allergen),
+            });
+        }
+    }
+    for diet in &needs.diets {
+        if !item.diets.contains(diet) {
+            conflicts.push(Conflict {
+                ingredient: item.name.clone(),
+                problem: Problem::NotSuitable(*diet),
+            });
+        }
+    }
+    conflicts
+}
+
+// The parts of a pizza you can eat. The size isn't an ingredient
+fn ingredients(pizza: &Pizza) -> Vec<&MenuItem> {
+    pizza.components().into_iter()
+        .filter(|c| c.category != Category::Size)
+        .collect()
+}
+
+// Every part of the pizza that conflicts with the customer's needs
+pub fn check(pizza: &Pizza, needs: &DietaryNeeds) -> Vec<Conflict> {
+    ingredients(pizza).into_iter()
+        .flat_map(|item| item_conflicts(item, needs))
+        .collect()
+}
+
+// Every allergen anywhere on the pizza
+pub fn allergens(pizza: &Pizza) -> Vec<Allergen> {
+    Allergen::ALL.into_iter()
+        .filter(|a| ingredients(pizza).iter().any(|c| c.allergens.contains(a)))
+        .collect()
+}
+
+// The diets every part of the pizza is suitable for
+pub fn diets(pizza: &Pizza) -> Vec<Diet> {
+    Diet::ALL.into_iter()
+        .filter(|d| ingredients(pizza).iter().all(|c| c.diets.contains(d)))
+        .collect()
+}
+
+// A conflict on one pizza of an order, counting pizzas from 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderConflict {
+    pub pizza: usize,
+    pub conflict: Conflict,
+}
+
+impl fmt::Display for OrderConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pizza {} : {}", self.pizza, self.conflict)
+    }
+}
+
+// Returned when an order has something the customer is allergic to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected(pub Vec<OrderConflict>);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reasons: Vec<String> = self.0.iter().map(|c| c.to_string()).collect();
+        write!(f, "Unsafe for the customer : {}", reasons.join("; "))
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+// Check an order against the needs the customer gave us
+// Allergens reject the whole order. Otherwise any diet conflicts
+// are returned as warnings
+pub fn screen(order: &Order) -> Result<Vec<OrderConflict>, Rejected> {
+    let conflicts: Vec<OrderConflict> = order.pizzas.iter().enumerate()
+        .flat_map(|(i, pizza)| check(pizza, &order.needs).into_iter()
+            .map(move |conflict| OrderConflict { pizza: i + 1, conflict }))
+        .collect();
+
+    let (rejections, warnings): (Vec<_>, Vec<_>) =
+        conflicts.into_iter().partition(|c| c.conflict.is_rejection());
+    if rejections.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(Rejected(rejections))
+    }
+}
@@ -0,0 +1,189 @@
+// The order history keeps every finished order in a file so it
+// is still there the next time the program runs
+// Each line of the file is one order written as JSON (JSON lines)
+// Appending a line is all it takes to save an order and a broken
+// line can't damage the ones before it
+// An order saved again, like one voided after it was served, is a
+// new line and the last line for an order is the one that counts
+
+use super::date::Date;
+use super::json::Json;
+use super::order::{self, Order, OrderStatus};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Io(io::Error),
+    // A line in the file that isn't a valid order (lines start at 1)
+    BadLine { line: usize, message: String },
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::Io(e) => write!(f, "Order history file error : {}", e),
+            HistoryError::BadLine { line, message } =>
+                write!(f, "Order history line {} : {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+// Lets ? turn io errors into history errors
+impl From<io::Error> for HistoryError {
+    fn from(e: io::Error) -> HistoryError {
+        HistoryError::Io(e)
+    }
+}
+
+// Describes which orders you are looking for. Every filter you set
+// must match. Dates are inclusive and use the day the order was placed
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    from: Option<Date>,
+    to: Option<Date>,
+    topping: Option<String>,
+    customer: Option<String>,
+    status: Option<OrderStatus>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn from(mut self, date: Date) -> Query {
+        self.from = Some(date);
+        self
+    }
+
+    pub fn to(mut self, date: Date) -> Query {
+        self.to = Some(date);
+        self
+    }
+
+    // Only orders placed on this day
+    pub fn on(self, date: Date) -> Query {
+        self.from(date).to(date)
+    }
+
+    pub fn topping(mut self, topping: &str) -> Query {
+        self.topping = Some(String::from(topping));
+        self
+    }
+
+    pub fn customer(mut self, customer: &str) -> Query {
+        self.customer = Some(String::from(customer));
+        self
+    }
+
+    pub fn status(mut self, status: OrderStatus) -> Query {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn matches(&self, order: &Order) -> bool {
+        let day = Date::of(order.placed_at());
+        self.from.is_none_or(|from| day >= from)
+            && self.to.is_none_or(|to| day <= to)
+            && self.status.is_none_or(|status| order.status() == status)
+            && self.customer.as_ref().is_none_or(|name| {
+                order.customer.as_ref().is_some_and(|c| c.eq_ignore_ascii_case(name))
+            })
+            && self.topping.as_ref().is_none_or(|topping| {
+                order.pizzas.iter().any(|p| {
+                    p.toppings.iter().any(|t| t.name.eq_ignore_ascii_case(topping))
+                })
+            })
+    }
+}
+
+pub struct OrderStore {
+    path: PathBuf,
+    orders: Vec<Order>,
+}
+
+impl OrderStore {
+    // Load every order saved in the file. A missing file just means
+    // nothing has been saved yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<OrderStore, HistoryError> {
+        let path = path.as_ref().to_path_buf();
+        let mut orders = Vec::new();
+
+        let file = match File::open(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(HistoryError::Io(e)),
+        };
+
+        if let Some(file) = file {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json = Json::parse(&line).map_err(|e| HistoryError::BadLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                })?;
+                let order = Order::from_json(&json).ok_or(HistoryError::BadLine {
+                    line: i + 1,
+                    message: String::from("Not a valid order"),
+                })?;
+                keep(&mut orders, order);
+            }
+        }
+
+        // New orders must not reuse a number from the file
+        if let Some(max) = orders.iter().map(|o| o.id).max() {
+            order::reserve_ids_through(max);
+        }
+
+        Ok(OrderStore { path, orders })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Save an order to the end of the file and keep it in memory
+    pub fn append(&mut self, order: &Order) -> Result<(), HistoryError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", order.to_json())?;
+        keep(&mut self.orders, order.clone());
+        Ok(())
+    }
+
+    // Every order oldest first by when it was first saved
+    pub fn all(&self) -> &[Order] {
+        &self.orders
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Order> {
+        self.orders.iter().find(|o| o.id == id)
+    }
+
+    pub fn find(&self, query: &Query) -> Vec<&Order> {
+        self.orders.iter().filter(|o| query.matches(o)).collect()
+    }
+
+    // Served orders placed on a day, answers "what sold yesterday"
+    pub fn sold_on(&self, date: Date) -> Vec<&Order> {
+        self.find(&Query::new().on(date).status(OrderStatus::Served))
+    }
+}
+
+// A later copy of an order replaces the earlier one in place
+fn keep(orders: &mut Vec<Order>, order: Order) {
+    match orders.iter_mut().find(|o| o.id == order.id) {
+        Some(earlier) => *earlier = order,
+        None => orders.push(order),
+    }
+}
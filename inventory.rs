@@ -0,0 +1,249 @@
+// Inventory keeps track of how much of each ingredient is in the
+// walk in cooler. Recipes say how much of each ingredient goes
+// into every menu item so making a pizza can take it out of stock
+
+// All amounts are in grams
+
+use super::menu::{Category, MenuItem};
+use super::order::Order;
+use super::pizza_order::Pizza;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryError {
+    // A menu item that nobody wrote a recipe for
+    MissingRecipe(String),
+    OutOfStock { ingredient: String, needed: u32, available: u32 },
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryError::MissingRecipe(item) => write!(f, "There is no recipe for {}", item),
+            InventoryError::OutOfStock { ingredient, needed, available } =>
+                write!(f, "Not enough {} : need {}g but only {}g left",
+                    ingredient, needed, available),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+// Raised when an ingredient drops to or below its reorder level
+#[derive(Debug, Clone, PartialEq)]
+pub struct LowStock {
+    pub ingredient: String,
+    pub remaining: u32,
+    pub threshold: u32,
+}
+
+impl fmt::Display for LowStock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Low stock : {} has {}g left (reorder at {}g)",
+            self.ingredient, self.remaining, self.threshold)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Inventory {
+    stock: HashMap<String, u32>,
+    // When stock reaches this amount we need to reorder
+    thresholds: HashMap<String, u32>,
+    // Grams of each ingredient a menu item uses on a small pizza
+    recipes: HashMap<String, Vec<(String, u32)>>,
+    // Sizes scale every recipe by a percent (a large uses 200%)
+    size_scale: HashMap<String, u32>,
+    // Ingredients every pizza gets no matter what is ordered
+    base: Vec<(String, u32)>,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory {
+            stock: HashMap::new(),
+            thresholds: HashMap::new(),
+            recipes: HashMap::new(),
+            size_scale: HashMap::new(),
+            base: Vec::new(),
+        }
+    }
+
+    // Recipes for everything on the standard menu and a fresh delivery
+    pub fn standard() -> Inventory {
+        let mut inv = Inventory::new();
+
+        inv.set_size_scale("small", 100);
+        inv.set_size_scale("medium", 150);
+        inv.set_size_scale("large", 200);
+
+        inv.set_base(&[("tomato sauce", 80)]);
+
+        inv.set_recipe("regular dough", &[("dough", 250)]);
+        inv.set_recipe("thin crust", &[("dough", 180)]);
+        inv.set_recipe("whole wheat", &[("wheat dough", 250)]);
+        inv.set_recipe("stuffed crust", &[("dough", 250), ("mozzarella", 60)]);
+        inv.set_recipe("gluten free crust", &[("gluten free dough", 220)]);
+
+        inv.set_recipe("mozzarella", &[("mozzarella", 120)]);
+        inv.set_recipe("cheddar", &[("cheddar", 120)]);
+        inv.set_recipe("provolone", &[("provolone", 120)]);
+        inv.set_recipe("vegan cheese", &[("vegan cheese", 120)]);
+
+        inv.set_recipe("veggies", &[("peppers", 40), ("onions", 40)]);
+        inv.set_recipe("pepperoni", &[("pepperoni", 60)]);
+        inv.set_recipe("mushrooms", &[("mushrooms", 60)]);
+        inv.set_recipe("olives", &[("olives", 40)]);
+        inv.set_recipe("sausage", &[("sausage", 80)]);
+        inv.set_recipe("pineapple", &[("pineapple", 70)]);
+
+        // Start with 5kg of everything and reorder at 1kg
+        let ingredients = ["tomato sauce", "dough", "wheat dough", "gluten free dough",
+            "mozzarella", "cheddar", "provolone", "vegan cheese", "peppers", "onions",
+            "pepperoni", "mushrooms", "olives", "sausage", "pineapple"];
+        for ingredient in ingredients {
+            inv.restock(ingredient, 5_000);
+            inv.set_threshold(ingredient, 1_000);
+        }
+
+        inv
+    }
+
+    pub fn set_recipe(&mut self, item: &str, ingredients: &[(&str, u32)]) {
+        self.recipes.insert(item.to_lowercase(), to_owned(ingredients));
+    }
+
+    pub fn set_base(&mut self, ingredients: &[(&str, u32)]) {
+        self.base = to_owned(ingredients);
+    }
+
+    pub fn set_size_scale(&mut self, size: &str, percent: u32) {
+        self.size_scale.insert(size.to_lowercase(), percent);
+    }
+
+    // The recipe a menu item has by name if it has one
+    pub fn recipe_for(&self, item: &str) -> Option<&[(String, u32)]> {
+        self.recipes.get(&item.to_lowercase()).map(|r| r.as_slice())
+    }
+
+    pub fn size_scale(&self, size: &str) -> Option<u32> {
+        self.size_scale.get(&size.to_lowercase()).copied()
+    }
+
+    pub fn base(&self) -> &[(String, u32)] {
+        &self.base
+    }
+
+    pub fn set_threshold(&mut self, ingredient: &str, grams: u32) {
+        self.thresholds.insert(String::from(ingredient), grams);
+    }
+
+    // A delivery came in
+    pub fn restock(&mut self, ingredient: &str, grams: u32) {
+        *self.stock.entry(String::from(ingredient)).or_insert(0) += grams;
+    }
+
+    pub fn stock(&self, ingredient: &str) -> u32 {
+        self.stock.get(ingredient).copied().unwrap_or(0)
+    }
+
+    // Every ingredient that is at or below its reorder level
+    pub fn low_stock(&self) -> Vec<LowStock> {
+        let mut low: Vec<LowStock> = self.thresholds.iter()
+            .filter(|(ingredient, &threshold)| self.stock(ingredient) <= threshold)
+            .map(|(ingredient, &threshold)| LowStock {
+                ingredient: ingredient.clone(),
+                remaining: self.stock(ingredient),
+                threshold,
+            })
+            .collect();
+        low.sort_by(|a, b| a.ingredient.cmp(&b.ingredient));
+        low
+    }
+
+    fn recipe(&self, item: &MenuItem) -> Result<&Vec<(String, u32)>, InventoryError> {
+        self.recipes.get(&item.name.to_lowercase())
+            .ok_or_else(|| InventoryError::MissingRecipe(item.name.clone()))
+    }
+
+    // Grams of each ingredient one part of a pizza uses in a size
+    // The size itself stands for the base every pizza gets
+    pub fn item_requirements(&self, item: &MenuItem, size: &str)
+                             -> Result<Vec<(String, u32)>, InventoryError> {
+        let scale = *self.size_scale.get(&size.to_lowercase())
+            .ok_or_else(|| InventoryError::MissingRecipe(String::from(size)))?;
+        let recipe = if item.category == Category::Size { &self.base } else { self.recipe(item)? };
+        Ok(recipe.iter().map(|(ingredient, grams)| (ingredient.clone(), grams * scale / 100)).collect())
+    }
+
+    // Grams of each ingredient needed to make one pizza
+    pub fn requirements(&self, pizza: &Pizza) -> Result<HashMap<String, u32>, InventoryError> {
+        let mut needed: HashMap<String, u32> = HashMap::new();
+        for item in pizza.components() {
+            for (ingredient, grams) in self.item_requirements(item, &pizza.size.name)? {
+                *needed.entry(ingredient).or_insert(0) += grams;
+            }
+        }
+        Ok(needed)
+    }
+
+    // Grams of each ingredient needed for every pizza in an order
+    pub fn order_requirements(&self, order: &Order) -> Result<HashMap<String, u32>, InventoryError> {
+        let mut needed: HashMap<String, u32> = HashMap::new();
+        for pizza in &order.pizzas {
+            for (ingredient, grams) in self.requirements(pizza)? {
+                *needed.entry(ingredient).or_insert(0) += grams;
+            }
+        }
+        Ok(needed)
+    }
+
+    // Check there is enough of everything without taking anything
+    pub fn check(&self, order: &Order) -> Result<(), InventoryError> {
+        let needed = self.order_requirements(order)?;
+
+        // Sort so the same ingredient is always reported first
+        let mut needed: Vec<(String, u32)> = needed.into_iter().collect();
+        needed.sort();
+
+        for (ingredient, grams) in needed {
+            let available = self.stock(&ingredient);
+            if available < grams {
+                return Err(InventoryError::OutOfStock { ingredient, needed: grams, available });
+            }
+        }
+        Ok(())
+    }
+
+    // Take the ingredients for an order out of stock
+    // Nothing is taken unless everything is available. Returns the
+    // ingredients that just dropped to their reorder level
+    pub fn deduct(&mut self, order: &Order) -> Result<Vec<LowStock>, InventoryError> {
+        self.check(order)?;
+        let needed = self.order_requirements(order)?;
+        let was_low = self.low_stock();
+
+        for (ingredient, grams) in needed {
+            if let Some(stock) = self.stock.get_mut(&ingredient) {
+                *stock -= grams;
+            }
+        }
+
+        Ok(self.low_stock().into_iter()
+            .filter(|low| !was_low.iter().any(|w| w.ingredient == low.ingredient))
+            .collect())
+    }
+
+    // Put back what deduct took for an order that was stopped before
+    // the ingredients were used
+    pub fn return_stock(&mut self, order: &Order) -> Result<(), InventoryError> {
+        for (ingredient, grams) in self.order_requirements(order)? {
+            self.restock(&ingredient, grams);
+        }
+        Ok(())
+    }
+}
+
+fn to_owned(ingredients: &[(&str, u32)]) -> Vec<(String, u32)> {
+    ingredients.iter().map(|(name, grams)| (String::from(*name), *grams)).collect()
+}
@@ -0,0 +1,398 @@
+// The kitchen is where orders are cooked
+// Orders wait in a queue and a team of cook threads each take
+// the next order, cook it and hand it off to be served
+
+// This works like the bank example in main.rs. Many threads share
+// the queue and the inventory so they are wrapped in Arc<Mutex<T>>
+
+use super::delivery::Dispatch;
+use super::inventory::{Inventory, InventoryError};
+use super::order::{Order, OrderError, OrderStatus};
+use super::staff::{Role, Roster};
+use super::tickets::{Ticket, TicketPrinter};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+// Called with each order once it is ready to be served
+// It must be Send + Sync because every cook thread calls it
+type OnReady = Arc<dyn Fn(&mut Order) + Send + Sync>;
+
+// The latest copy of every order the kitchen has been given so
+// anyone can see how far along an order is
+type Board = Arc<Mutex<HashMap<u32, Order>>>;
+
+// Where tickets print. None until a printer is attached
+type Printer = Arc<Mutex<Option<TicketPrinter>>>;
+
+// Who is cooking. None until a roster is attached
+type Staff = Arc<Mutex<Option<Arc<Mutex<Roster>>>>>;
+
+// Who delivers. None until a dispatch is attached
+type Drivers = Arc<Mutex<Option<Arc<Mutex<Dispatch>>>>>;
+
+// Everything the cooks share
+#[derive(Clone)]
+struct Station {
+    inventory: Arc<Mutex<Inventory>>,
+    board: Board,
+    printer: Printer,
+    staff: Staff,
+    drivers: Drivers,
+}
+
+#[derive(Debug)]
+pub enum KitchenError {
+    // The kitchen has shut down so the order was handed back
+    // Boxed so every Result from the kitchen stays small
+    Closed(Box<Order>),
+    UnknownOrder(u32),
+    // The order is already out of the kitchen
+    TooLateToCancel(u32, OrderStatus),
+    // A change the order wouldn't take
+    CantChange(u32, OrderError),
+}
+
+impl fmt::Display for KitchenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KitchenError::Closed(order) =>
+                write!(f, "The kitchen is closed, order {} was not accepted", order.id),
+            KitchenError::UnknownOrder(id) => write!(f, "The kitchen has no order {}", id),
+            KitchenError::TooLateToCancel(id, status) =>
+                write!(f, "Order {} is {} and can't be cancelled", id, status.name()),
+            KitchenError::CantChange(id, e) => write!(f, "Order {} : {}", id, e),
+        }
+    }
+}
+
+impl std::error::Error for KitchenError {}
+
+// A cook is a thread that keeps taking orders off the queue
+struct Cook {
+    id: usize,
+    handle: JoinHandle<()>,
+}
+
+pub struct Kitchen {
+    // A channel sends values from one thread to another
+    // When every Sender is dropped the cooks know no more orders
+    // are coming. It is an Option so shutdown can drop it
+    queue: Option<Sender<Order>>,
+    cooks: Vec<Cook>,
+    inventory: Arc<Mutex<Inventory>>,
+    board: Board,
+    printer: Printer,
+    staff: Staff,
+    drivers: Drivers,
+    // The store this kitchen is in. Orders are tagged with it
+    location: Option<String>,
+}
+
+impl Kitchen {
+    // Open the kitchen with a number of cooks
+    // bake_time is how long each order spends in the oven
+    // Ingredients are taken from inventory as each order is prepared
+    pub fn open<F>(cooks: usize, bake_time: Duration,
+                   inventory: Arc<Mutex<Inventory>>, on_ready: F) -> Kitchen
+    where
+        F: Fn(&mut Order) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let board: Board = Arc::new(Mutex::new(HashMap::new()));
+        let printer: Printer = Arc::new(Mutex::new(None));
+        let staff: Staff = Arc::new(Mutex::new(None));
+        let drivers: Drivers = Arc::new(Mutex::new(None));
+        let station = Station {
+            inventory: inventory.clone(),
+            board: board.clone(),
+            printer: printer.clone(),
+            staff: staff.clone(),
+            drivers: drivers.clone(),
+        };
+
+        // Only one cook at a time may take from the queue
+        let receiver: Arc<Mutex<Receiver<Order>>> = Arc::new(Mutex::new(receiver));
+        let on_ready: OnReady = Arc::new(on_ready);
+
+        let cooks = (1..=cooks.max(1)).map(|id| {
+            let receiver = receiver.clone();
+            let station = station.clone();
+            let on_ready = on_ready.clone();
+            let handle = thread::spawn(move || loop {
+                // The lock is released at the end of this statement so
+                // other cooks can take orders while this one cooks
+                let next = receiver.lock().unwrap().recv();
+                match next {
+                    Ok(order) => cook(id, order, bake_time, &station, &on_ready),
+                    // The queue is closed and empty
+                    Err(_) => break,
+                }
+            });
+            Cook { id, handle }
+        }).collect();
+
+        Kitchen {
+            queue: Some(sender),
+            cooks,
+            inventory,
+            board,
+            printer,
+            staff,
+            drivers,
+            location: None,
+        }
+    }
+
+    // Tag every order submitted from now on with a store's code
+    pub fn set_location(&mut self, code: &str) {
+        self.location = Some(String::from(code));
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    // Print a ticket for every order from now on. Attaching another
+    // printer replaces this one
+    pub fn attach_printer(&self, printer: TicketPrinter) {
+        *self.printer.lock().unwrap() = Some(printer);
+    }
+
+    // Give every order from now on to a cook on the roster when a
+    // cook thread picks it up
+    pub fn attach_roster(&self, roster: Arc<Mutex<Roster>>) {
+        *self.staff.lock().unwrap() = Some(roster);
+    }
+
+    // Call off the delivery of any order the kitchen can't make
+    pub fn attach_dispatch(&self, dispatch: Arc<Mutex<Dispatch>>) {
+        *self.drivers.lock().unwrap() = Some(dispatch);
+    }
+
+    // The roster attached to the kitchen if there is one
+    pub fn roster(&self) -> Option<Arc<Mutex<Roster>>> {
+        self.staff.lock().unwrap().clone()
+    }
+
+    // Shared access to the stock so it can be checked or restocked
+    pub fn inventory(&self) -> Arc<Mutex<Inventory>> {
+        self.inventory.clone()
+    }
+
+    // Check the order can be made before promising it to a customer
+    pub fn can_make(&self, order: &Order) -> Result<(), InventoryError> {
+        self.inventory.lock().unwrap().check(order)
+    }
+
+    // Add an order to the back of the queue. Orders without a store
+    // are tagged with this kitchen's
+    pub fn submit(&self, mut order: Order) -> Result<(), KitchenError> {
+        if order.location.is_none() {
+            order.location = self.location.clone();
+        }
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return Err(KitchenError::Closed(Box::new(order))),
+        };
+        let id = order.id;
+        self.board.lock().unwrap().insert(id, order.clone());
+        queue.send(order).map_err(|e| {
+            self.board.lock().unwrap().remove(&id);
+            KitchenError::Closed(Box::new(e.0))
+        })
+    }
+
+    // A copy of the order as it stands right now
+    pub fn order(&self, id: u32) -> Option<Order> {
+        self.board.lock().unwrap().get(&id).cloned()
+    }
+
+    // Cancel an order that is still waiting or being cooked
+    // The cook working on it stops at their next step and puts
+    // back any ingredients it took
+    pub fn cancel(&self, id: u32) -> Result<(), KitchenError> {
+        self.stop(id, OrderStatus::Cancelled)
+    }
+
+    // Void works the same way but the order was taken off the bill
+    // by a manager rather than the customer changing their mind
+    // Orders still cooking are stopped. Served ones just come off
+    // the bill so the board shows them voided
+    pub fn void(&self, id: u32) -> Result<(), KitchenError> {
+        {
+            let mut board = self.board.lock().unwrap();
+            let order = board.get_mut(&id).ok_or(KitchenError::UnknownOrder(id))?;
+            if order.status() == OrderStatus::Served {
+                return order.void().map_err(|e| KitchenError::CantChange(id, e));
+            }
+        }
+        self.stop(id, OrderStatus::Voided)
+    }
+
+    fn stop(&self, id: u32, status: OrderStatus) -> Result<(), KitchenError> {
+        let mut board = self.board.lock().unwrap();
+        let order = board.get_mut(&id).ok_or(KitchenError::UnknownOrder(id))?;
+        match order.status() {
+            OrderStatus::Placed | OrderStatus::Preparing | OrderStatus::Baking =>
+                order.advance(status).map_err(|_| KitchenError::TooLateToCancel(id, order.status())),
+            now => Err(KitchenError::TooLateToCancel(id, now)),
+        }
+    }
+
+    // Change an order that is still waiting for a cook. The cook
+    // picks up the changed copy. Returns the order after the change
+    pub fn modify<F>(&self, id: u32, change: F) -> Result<Order, KitchenError>
+    where
+        F: FnOnce(&mut Order) -> Result<(), OrderError>,
+    {
+        let mut board = self.board.lock().unwrap();
+        let order = board.get_mut(&id).ok_or(KitchenError::UnknownOrder(id))?;
+        // Changed on a copy so a failed change leaves the order alone
+        let mut changed = order.clone();
+        change(&mut changed).map_err(|e| KitchenError::CantChange(id, e))?;
+        *order = changed.clone();
+        Ok(changed)
+    }
+
+    // Stop taking orders, let the cooks finish everything in the
+    // queue and wait for them to go home
+    pub fn shutdown(mut self) {
+        self.close();
+    }
+
+    fn close(&mut self) {
+        // Dropping the sender closes the queue
+        drop(self.queue.take());
+        for cook in self.cooks.drain(..) {
+            if cook.handle.join().is_err() {
+                println!("Cook {} stopped unexpectedly", cook.id);
+            }
+        }
+    }
+}
+
+// Drop runs when the kitchen goes out of scope so orders are
+// never left behind even if shutdown isn't called
+impl Drop for Kitchen {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+// Put the cook's copy of an order on the board. Returns false if the
+// order was cancelled or voided while the cook was busy so they stop
+fn post(board: &Mutex<HashMap<u32, Order>>, order: &Order) -> bool {
+    let mut board = board.lock().unwrap();
+    if let Some(posted) = board.get(&order.id) {
+        if matches!(posted.status(), OrderStatus::Cancelled | OrderStatus::Voided) {
+            return false;
+        }
+    }
+    board.insert(order.id, order.clone());
+    true
+}
+
+// A cook starts on an order. The board has the latest copy with any
+// changes made while it waited. Moving it to Preparing in the same
+// lock means no change can sneak in after the cook has read it
+// Returns None if the order was cancelled or voided while waiting
+fn pick_up(board: &Mutex<HashMap<u32, Order>>, order: Order) -> Option<Order> {
+    let mut board = board.lock().unwrap();
+    let mut order = board.get(&order.id).cloned().unwrap_or(order);
+    order.start_preparing().ok()?;
+    board.insert(order.id, order.clone());
+    Some(order)
+}
+
+// The cook on the roster who makes the order. When nobody is on
+// shift the manager does it
+fn assign_cook(staff: &Staff, order_id: u32) {
+    let Some(roster) = staff.lock().unwrap().clone() else {
+        return;
+    };
+    let mut roster = roster.lock().unwrap();
+    match roster.assign(order_id, Role::Cook, SystemTime::now()) {
+        Ok(employee) => println!("{} is the cook for order {}", employee.name, order_id),
+        Err(e) => println!("{} so the manager is the cook for order {}", e, order_id),
+    }
+}
+
+// Nobody should wait on a driver for an order that won't be made
+fn cancel_delivery(drivers: &Drivers, order: &Order) {
+    if order.delivery.is_none() {
+        return;
+    }
+    if let Some(dispatch) = drivers.lock().unwrap().as_ref() {
+        if let Err(e) = dispatch.lock().unwrap().cancel(order.id) {
+            println!("The delivery for order {} wasn't called off : {}", order.id, e);
+        }
+    }
+}
+
+// Takes one order from Placed to Ready
+fn cook(cook_id: usize, order: Order, bake_time: Duration, station: &Station,
+        on_ready: &OnReady) {
+    let Station { inventory, board, printer, staff, drivers } = station;
+    let id = order.id;
+    let Some(mut order) = pick_up(board, order) else {
+        println!("Cook {} skipped cancelled order {}", cook_id, id);
+        return;
+    };
+    println!("Cook {} is making order {}", cook_id, order.id);
+    assign_cook(staff, order.id);
+
+    // Another order may have used the last of something since this
+    // one was taken so the stock is checked again here
+    let taken = inventory.lock().unwrap().deduct(&order);
+    match taken {
+        Ok(alerts) => {
+            for alert in alerts {
+                println!("{}", alert);
+            }
+        }
+        Err(e) => {
+            println!("Cook {} can't make order {} : {}", cook_id, order.id, e);
+            if order.cancel().is_ok() {
+                post(board, &order);
+                cancel_delivery(drivers, &order);
+            }
+            return;
+        }
+    }
+
+    // The ticket only prints for orders the kitchen can make
+    if let Some(printer) = printer.lock().unwrap().as_ref() {
+        if let Err(e) = printer.print(&Ticket::for_order(&order)) {
+            println!("The ticket for order {} didn't print : {}", order.id, e);
+        }
+    }
+
+    // Each step is posted so the board shows where the order is
+    // The wait is how long the step before it takes
+    let steps = [
+        (OrderStatus::Baking, Duration::ZERO),
+        (OrderStatus::Ready, bake_time),
+    ];
+    for (status, wait) in steps {
+        thread::sleep(wait);
+        if let Err(e) = order.advance(status) {
+            println!("Cook {} stopped order {} : {}", cook_id, order.id, e);
+            return;
+        }
+        if !post(board, &order) {
+            // A stopped order gives its ingredients back to the stock
+            if let Err(e) = inventory.lock().unwrap().return_stock(&order) {
+                println!("Order {} couldn't go back into stock : {}", order.id, e);
+            }
+            println!("Cook {} stopped cancelled order {}", cook_id, order.id);
+            return;
+        }
+    }
+
+    on_ready(&mut order);
+    post(board, &order);
+}
@@ -0,0 +1,152 @@
+// A franchise is a chain of stores. Each store has its own settings
+// file so the menu, prices, hours and tables can differ, and its
+// own stock, staff and till. Every order a store's kitchen takes is
+// tagged with the store's code so reports can be split by store or
+// rolled up for the whole chain, see analytics::Rollup
+
+use super::analytics::Rollup;
+use super::config::RestaurantConfig;
+use super::inventory::Inventory;
+use super::kitchen::Kitchen;
+use super::order::Order;
+use super::staff::Roster;
+use super::till::Till;
+use super::toml::LoadError;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum FranchiseError {
+    // Two stores can't share a code or their orders would mix
+    DuplicateLocation(String),
+    Load(LoadError),
+}
+
+impl fmt::Display for FranchiseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FranchiseError::DuplicateLocation(code) =>
+                write!(f, "There is already a store called {}", code),
+            FranchiseError::Load(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FranchiseError {}
+
+impl From<LoadError> for FranchiseError {
+    fn from(e: LoadError) -> FranchiseError {
+        FranchiseError::Load(e)
+    }
+}
+
+pub struct Location {
+    pub config: RestaurantConfig,
+    // Shared with the store's kitchen once it opens
+    pub inventory: Arc<Mutex<Inventory>>,
+    pub roster: Arc<Mutex<Roster>>,
+    pub till: Arc<Mutex<Till>>,
+}
+
+impl Location {
+    // A store with the stock from its settings file, the standard
+    // staff and a till that isn't saved
+    pub fn new(config: RestaurantConfig) -> Location {
+        let inventory = config.inventory.clone();
+        Location {
+            config,
+            inventory: Arc::new(Mutex::new(inventory)),
+            roster: Arc::new(Mutex::new(Roster::standard())),
+            till: Arc::new(Mutex::new(Till::new())),
+        }
+    }
+
+    pub fn with_inventory(mut self, inventory: Inventory) -> Location {
+        self.inventory = Arc::new(Mutex::new(inventory));
+        self
+    }
+
+    pub fn with_roster(mut self, roster: Roster) -> Location {
+        self.roster = Arc::new(Mutex::new(roster));
+        self
+    }
+
+    // Usually a till opened from the store's own file
+    pub fn with_till(mut self, till: Till) -> Location {
+        self.till = Arc::new(Mutex::new(till));
+        self
+    }
+
+    pub fn code(&self) -> &str {
+        &self.config.location
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    // Open this store's kitchen. It cooks from this store's stock,
+    // gives orders to this store's cooks and tags every order with
+    // this store's code
+    pub fn open_kitchen<F>(&self, cooks: usize, bake_time: Duration, on_ready: F) -> Kitchen
+    where
+        F: Fn(&mut Order) + Send + Sync + 'static,
+    {
+        let mut kitchen = Kitchen::open(cooks, bake_time, self.inventory.clone(), on_ready);
+        kitchen.set_location(self.code());
+        kitchen.attach_roster(self.roster.clone());
+        kitchen
+    }
+}
+
+pub struct Franchise {
+    locations: Vec<Location>,
+}
+
+impl Franchise {
+    pub fn new() -> Franchise {
+        Franchise { locations: Vec::new() }
+    }
+
+    // Each path is a settings file like restaurant.toml. Every file
+    // needs its own location code
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Franchise, FranchiseError> {
+        let mut franchise = Franchise::new();
+        for path in paths {
+            franchise.add(Location::new(RestaurantConfig::load(path.as_ref())?))?;
+        }
+        Ok(franchise)
+    }
+
+    pub fn add(&mut self, location: Location) -> Result<(), FranchiseError> {
+        if self.get(location.code()).is_some() {
+            return Err(FranchiseError::DuplicateLocation(String::from(location.code())));
+        }
+        self.locations.push(location);
+        Ok(())
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Location> {
+        self.locations.iter().find(|l| l.code() == code)
+    }
+
+    pub fn get_mut(&mut self, code: &str) -> Option<&mut Location> {
+        self.locations.iter_mut().find(|l| l.code() == code)
+    }
+
+    pub fn locations(&self) -> &[Location] {
+        &self.locations
+    }
+
+    // Sales for every store side by side with refunds looked up in
+    // each store's own till
+    pub fn rollup<'a, I: IntoIterator<Item = &'a Order>>(&self, orders: I) -> Rollup {
+        let open: Vec<(&str, MutexGuard<Till>)> = self.locations.iter()
+            .map(|l| (l.code(), l.till.lock().unwrap()))
+            .collect();
+        let tills: Vec<(&str, &Till)> = open.iter().map(|(code, till)| (*code, &**till)).collect();
+        Rollup::with_tills(orders, &tills)
+    }
+}
@@ -0,0 +1,414 @@
+// The loyalty program gives customers points for every dollar they
+// spend. Points can be spent on a later pizza and customers who
+// spend more reach tiers with better perks
+// Every change to a balance is a line in the ledger so staff can
+// see exactly where a customer's points came from. Like the order
+// history the ledger is a JSON lines file that is only added to
+
+use super::date;
+use super::json::Json;
+use super::menu::{format_price, format_rate, percent_of};
+use super::order::{Order, OrderStatus};
+use super::promotions::AppliedPromotion;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Points earned for every dollar spent before tax
+pub const EARN_PER_DOLLAR: u32 = 10;
+// Points it takes to get a dollar off, so points give 5% back
+pub const REDEEM_PER_DOLLAR: u32 = 200;
+// Points for signing up
+pub const WELCOME_BONUS: u32 = 100;
+
+#[derive(Debug)]
+pub enum LoyaltyError {
+    Io(io::Error),
+    BadLine { line: usize, message: String },
+    // The order has no customer name to give points to
+    NoCustomer(u32),
+    NotAMember(String),
+    AlreadyMember(String),
+    NotEnoughPoints { wanted: u32, balance: u32 },
+    NotServed(u32),
+    AlreadyEarned(u32),
+    AlreadyRedeemed(u32),
+    // Too few points to take a cent off the order
+    NothingToRedeem(u32),
+}
+
+impl fmt::Display for LoyaltyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoyaltyError::Io(e) => write!(f, "Loyalty ledger file error : {}", e),
+            LoyaltyError::BadLine { line, message } =>
+                write!(f, "Loyalty ledger line {} : {}", line, message),
+            LoyaltyError::NoCustomer(id) => write!(f, "Order {} has no customer name", id),
+            LoyaltyError::NotAMember(name) => write!(f, "{} isn't a loyalty member", name),
+            LoyaltyError::AlreadyMember(name) => write!(f, "{} is already a member", name),
+            LoyaltyError::NotEnoughPoints { wanted, balance } =>
+                write!(f, "Can't use {} points with only {} points", wanted, balance),
+            LoyaltyError::NotServed(id) => write!(f, "Order {} hasn't been served", id),
+            LoyaltyError::AlreadyEarned(id) => write!(f, "Order {} already earned points", id),
+            LoyaltyError::AlreadyRedeemed(id) => write!(f, "Points were already used on order {}", id),
+            LoyaltyError::NothingToRedeem(id) =>
+                write!(f, "Not enough points to take anything off order {}", id),
+        }
+    }
+}
+
+impl std::error::Error for LoyaltyError {}
+
+impl From<io::Error> for LoyaltyError {
+    fn from(e: io::Error) -> LoyaltyError {
+        LoyaltyError::Io(e)
+    }
+}
+
+// Tiers are reached by points earned over all time so spending
+// points never drops a customer to a lower tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Bronze,
+    Silver,
+    Gold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Perk {
+    // Basis points more points on every order
+    ExtraPoints(u32),
+    FreeDelivery,
+}
+
+impl fmt::Display for Perk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Perk::ExtraPoints(rate) =>
+                write!(f, "{} extra points", format_rate(*rate)),
+            Perk::FreeDelivery => write!(f, "Free delivery"),
+        }
+    }
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Bronze, Tier::Silver, Tier::Gold];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tier::Bronze => "Bronze",
+            Tier::Silver => "Silver",
+            Tier::Gold => "Gold",
+        }
+    }
+
+    // Lifetime points needed to reach the tier
+    pub fn threshold(&self) -> u32 {
+        match self {
+            Tier::Bronze => 0,
+            Tier::Silver => 1_000,
+            Tier::Gold => 5_000,
+        }
+    }
+
+    pub fn perks(&self) -> Vec<Perk> {
+        match self {
+            Tier::Bronze => vec![],
+            Tier::Silver => vec![Perk::ExtraPoints(2_500)],
+            Tier::Gold => vec![Perk::ExtraPoints(5_000), Perk::FreeDelivery],
+        }
+    }
+
+    pub fn for_points(lifetime: u32) -> Tier {
+        Tier::ALL.into_iter().rev().find(|t| lifetime >= t.threshold()).unwrap_or(Tier::Bronze)
+    }
+
+    pub fn has(&self, perk: Perk) -> bool {
+        self.perks().contains(&perk)
+    }
+
+    fn extra_points(&self) -> u32 {
+        self.perks().iter().map(|p| match p {
+            Perk::ExtraPoints(rate) => *rate,
+            _ => 0,
+        }).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Joined,
+    Earned,
+    Redeemed,
+    // A correction made by a manager
+    Adjusted,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 4] =
+        [EntryKind::Joined, EntryKind::Earned, EntryKind::Redeemed, EntryKind::Adjusted];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryKind::Joined => "Joined",
+            EntryKind::Earned => "Earned",
+            EntryKind::Redeemed => "Redeemed",
+            EntryKind::Adjusted => "Adjusted",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EntryKind> {
+        EntryKind::ALL.into_iter().find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// One change to a customer's points
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub customer: String,
+    pub at: SystemTime,
+    pub kind: EntryKind,
+    // Negative when points are spent or taken away
+    pub points: i64,
+    pub order_id: Option<u32>,
+    pub note: String,
+}
+
+impl LedgerEntry {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("customer", Json::string(&self.customer)),
+            ("at", Json::from(date::millis(self.at))),
+            ("kind", Json::string(self.kind.name())),
+            ("points", Json::from(self.points)),
+            ("order_id", Json::from(self.order_id)),
+            ("note", Json::string(&self.note)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<LedgerEntry> {
+        Some(LedgerEntry {
+            customer: String::from(json.get("customer")?.as_str()?),
+            at: date::from_millis(json.get("at")?.as_i64()?),
+            kind: EntryKind::from_name(json.get("kind")?.as_str()?)?,
+            points: json.get("points")?.as_i64()?,
+            order_id: json.get("order_id").and_then(|o| o.as_u32()),
+            note: String::from(json.get("note").and_then(|n| n.as_str()).unwrap_or("")),
+        })
+    }
+}
+
+pub struct Loyalty {
+    // Without a path the ledger is only kept in memory
+    path: Option<PathBuf>,
+    entries: Vec<LedgerEntry>,
+}
+
+impl Loyalty {
+    pub fn new() -> Loyalty {
+        Loyalty { path: None, entries: Vec::new() }
+    }
+
+    // Load the ledger. A missing file means nobody has joined yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Loyalty, LoyaltyError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+
+        let file = match File::open(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(LoyaltyError::Io(e)),
+        };
+
+        if let Some(file) = file {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json = Json::parse(&line).map_err(|e| LoyaltyError::BadLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                })?;
+                entries.push(LedgerEntry::from_json(&json).ok_or(LoyaltyError::BadLine {
+                    line: i + 1,
+                    message: String::from("Not a valid ledger entry"),
+                })?);
+            }
+        }
+
+        Ok(Loyalty { path: Some(path), entries })
+    }
+
+    // Write the entry to the file first so memory never has points
+    // the file doesn't
+    fn record(&mut self, entry: LedgerEntry) -> Result<&LedgerEntry, LoyaltyError> {
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", entry.to_json())?;
+        }
+        self.entries.push(entry);
+        Ok(&self.entries[self.entries.len() - 1])
+    }
+
+    fn entry(&self, customer: &str, kind: EntryKind, points: i64, order_id: Option<u32>,
+             note: String) -> LedgerEntry {
+        LedgerEntry {
+            customer: String::from(customer),
+            at: SystemTime::now(),
+            kind,
+            points,
+            order_id,
+            note,
+        }
+    }
+
+    pub fn is_member(&self, customer: &str) -> bool {
+        self.entries.iter().any(|e| e.customer.eq_ignore_ascii_case(customer))
+    }
+
+    fn member(&self, customer: &str) -> Result<(), LoyaltyError> {
+        if self.is_member(customer) {
+            Ok(())
+        } else {
+            Err(LoyaltyError::NotAMember(String::from(customer)))
+        }
+    }
+
+    pub fn join(&mut self, customer: &str) -> Result<&LedgerEntry, LoyaltyError> {
+        if self.is_member(customer) {
+            return Err(LoyaltyError::AlreadyMember(String::from(customer)));
+        }
+        let entry = self.entry(customer, EntryKind::Joined, WELCOME_BONUS as i64, None,
+            String::from("Welcome bonus"));
+        self.record(entry)
+    }
+
+    // Every change to a customer's points, oldest first
+    pub fn ledger(&self, customer: &str) -> Vec<&LedgerEntry> {
+        self.entries.iter().filter(|e| e.customer.eq_ignore_ascii_case(customer)).collect()
+    }
+
+    pub fn balance(&self, customer: &str) -> u32 {
+        let total: i64 = self.ledger(customer).iter().map(|e| e.points).sum();
+        total.max(0) as u32
+    }
+
+    // Points ever given, which is what decides the tier
+    pub fn lifetime(&self, customer: &str) -> u32 {
+        self.ledger(customer).iter()
+            .filter(|e| matches!(e.kind, EntryKind::Joined | EntryKind::Earned))
+            .map(|e| e.points.max(0) as u32)
+            .sum()
+    }
+
+    pub fn tier(&self, customer: &str) -> Tier {
+        Tier::for_points(self.lifetime(customer))
+    }
+
+    // True if the order's customer is a member with the perk
+    pub fn has_perk(&self, order: &Order, perk: Perk) -> bool {
+        order.customer.as_deref()
+            .is_some_and(|c| self.is_member(c) && self.tier(c).has(perk))
+    }
+
+    fn recorded(&self, order_id: u32, kind: EntryKind) -> bool {
+        self.entries.iter().any(|e| e.order_id == Some(order_id) && e.kind == kind)
+    }
+
+    // Give points for a served order. spent is what the customer paid
+    // in cents before tax, tip and delivery
+    pub fn earn(&mut self, order: &Order, spent: u32) -> Result<u32, LoyaltyError> {
+        let customer = order.customer.clone().ok_or(LoyaltyError::NoCustomer(order.id))?;
+        self.member(&customer)?;
+        if order.status() != OrderStatus::Served {
+            return Err(LoyaltyError::NotServed(order.id));
+        }
+        if self.recorded(order.id, EntryKind::Earned) {
+            return Err(LoyaltyError::AlreadyEarned(order.id));
+        }
+
+        let tier = self.tier(&customer);
+        let base = spent / 100 * EARN_PER_DOLLAR;
+        let points = base + percent_of(base, tier.extra_points());
+        let note = format!("{} spent as {}", format_price(spent), tier.name());
+        let entry = self.entry(&customer, EntryKind::Earned, points as i64, Some(order.id), note);
+        self.record(entry)?;
+        Ok(points)
+    }
+
+    // Spend the points the customer asked for on the order. Points
+    // pay for one pizza at most, the priciest one, and never more
+    // than is left to pay. Only the points needed are taken
+    pub fn redeem(&mut self, order: &Order, left_to_pay: u32)
+                  -> Result<AppliedPromotion, LoyaltyError> {
+        let customer = order.customer.clone().ok_or(LoyaltyError::NoCustomer(order.id))?;
+        self.member(&customer)?;
+        if self.recorded(order.id, EntryKind::Redeemed) {
+            return Err(LoyaltyError::AlreadyRedeemed(order.id));
+        }
+        let balance = self.balance(&customer);
+        if order.points > balance {
+            return Err(LoyaltyError::NotEnoughPoints { wanted: order.points, balance });
+        }
+
+        let pizza = order.pizzas.iter().map(|p| p.price()).max().unwrap_or(0);
+        let most = pizza.min(left_to_pay) * REDEEM_PER_DOLLAR / 100;
+        // Only whole cents come off so points that would be lost in
+        // the rounding stay with the customer
+        let points = order.points.min(most);
+        let points = points - points % (REDEEM_PER_DOLLAR / 100);
+        let amount = points * 100 / REDEEM_PER_DOLLAR;
+        if amount == 0 {
+            return Err(LoyaltyError::NothingToRedeem(order.id));
+        }
+
+        let note = format!("{} off", format_price(amount));
+        let entry = self.entry(&customer, EntryKind::Redeemed, -(points as i64), Some(order.id),
+            note.clone());
+        self.record(entry)?;
+        Ok(AppliedPromotion {
+            name: String::from("Loyalty points"),
+            amount,
+            explanation: format!("{} points for {}", points, note),
+        })
+    }
+
+    // A manager adds or takes away points. The reason is kept in the
+    // ledger so every change can be explained later
+    pub fn adjust(&mut self, customer: &str, points: i64, reason: &str)
+                  -> Result<&LedgerEntry, LoyaltyError> {
+        self.member(customer)?;
+        let balance = self.balance(customer) as i64;
+        if balance + points < 0 {
+            return Err(LoyaltyError::NotEnoughPoints {
+                wanted: points.unsigned_abs() as u32,
+                balance: balance as u32,
+            });
+        }
+        let entry = self.entry(customer, EntryKind::Adjusted, points, None, String::from(reason));
+        self.record(entry)
+    }
+
+    // The customer's ledger with a running balance
+    pub fn statement(&self, customer: &str) -> String {
+        let tier = self.tier(customer);
+        let mut lines = vec![format!("{} : {} member with {} points", customer, tier.name(),
+            self.balance(customer))];
+        let perks: Vec<String> = tier.perks().iter().map(|p| p.to_string()).collect();
+        if !perks.is_empty() {
+            lines.push(format!("Perks : {}", perks.join(", ")));
+        }
+
+        let mut balance = 0;
+        for e in self.ledger(customer) {
+            balance += e.points;
+            let order = e.order_id.map_or(String::new(), |id| format!("order {}", id));
+            lines.push(format!("{}  {:<9}{:>7}{:>8}  {:<10}{}", date::Date::of(e.at),
+                e.kind.name(), e.points, balance, order, e.note));
+        }
+        lines.join("\n")
+    }
+}
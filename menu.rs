@@ -0,0 +1,269 @@
+// The menu lists everything a customer can choose for a pizza
+// Every item belongs to a category (size, crust, cheese or topping)
+// and has its own price
+
+use super::dietary::{Allergen, Diet};
+use super::json::Json;
+
+// Prices are stored as whole cents in a u32 instead of a f32
+// Adding up floats can give you results like 12.499999 which
+// you never want to show a paying customer
+
+// Deriving lets the compiler write common traits for us
+// Copy and Clone let us duplicate the value, PartialEq lets us
+// compare with ==, Debug lets us print with {:?}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Size,
+    Crust,
+    Cheese,
+    Topping,
+}
+
+impl Category {
+    // Every category in the order they are printed on the menu
+    pub const ALL: [Category; 4] = [
+        Category::Size,
+        Category::Crust,
+        Category::Cheese,
+        Category::Topping,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Size => "Size",
+            Category::Crust => "Crust",
+            Category::Cheese => "Cheese",
+            Category::Topping => "Topping",
+        }
+    }
+
+    // The category with this name ignoring case
+    pub fn from_name(name: &str) -> Option<Category> {
+        Category::ALL.into_iter().find(|c| c.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// A single thing that can be ordered and what it costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+    pub name: String,
+    pub category: Category,
+    pub price: u32,
+    // Allergens in the item and diets it is suitable for
+    // An item with no diets listed isn't safe to call vegan or
+    // vegetarian so new items have to opt in
+    pub allergens: Vec<Allergen>,
+    pub diets: Vec<Diet>,
+}
+
+impl MenuItem {
+    pub fn new(name: &str, category: Category, price: u32) -> MenuItem {
+        MenuItem {
+            name: String::from(name),
+            category,
+            price,
+            allergens: Vec::new(),
+            diets: Vec::new(),
+        }
+    }
+
+    // Chain these after new to tag an item
+    pub fn contains(mut self, allergens: &[Allergen]) -> MenuItem {
+        self.allergens.extend_from_slice(allergens);
+        self
+    }
+
+    pub fn suits(mut self, diets: &[Diet]) -> MenuItem {
+        self.diets.extend_from_slice(diets);
+        self
+    }
+
+    // Labels for the menu like (dairy) V GF
+    pub fn tags(&self) -> String {
+        let mut tags = Vec::new();
+        if !self.allergens.is_empty() {
+            let names: Vec<&str> = self.allergens.iter().map(|a| a.name()).collect();
+            tags.push(format!("({})", names.join(", ")));
+        }
+        tags.extend(self.diets.iter().map(|d| String::from(d.label())));
+        tags.join(" ")
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("name", Json::string(&self.name)),
+            ("category", Json::string(self.category.name())),
+            ("price", Json::from(self.price)),
+            ("allergens", Json::Array(self.allergens.iter().map(|a| Json::string(a.name())).collect())),
+            ("diets", Json::Array(self.diets.iter().map(|d| Json::string(d.name())).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<MenuItem> {
+        let names = |key: &str| -> Vec<String> {
+            json.get(key).and_then(|v| v.as_array()).map_or(Vec::new(), |items| {
+                items.iter().filter_map(|i| i.as_str()).map(String::from).collect()
+            })
+        };
+        Some(MenuItem {
+            name: String::from(json.get("name")?.as_str()?),
+            category: Category::from_name(json.get("category")?.as_str()?)?,
+            price: json.get("price")?.as_u32()?,
+            allergens: names("allergens").iter().filter_map(|a| Allergen::from_name(a)).collect(),
+            diets: names("diets").iter().filter_map(|d| Diet::from_name(d)).collect(),
+        })
+    }
+}
+
+// What a pizza gets when the customer doesn't say
+#[derive(Debug, Clone, PartialEq)]
+pub struct House {
+    pub crust: String,
+    pub cheese: String,
+    // The size of a lunch pizza
+    pub lunch_size: String,
+}
+
+// The full catalog the restaurant sells from
+pub struct Menu {
+    items: Vec<MenuItem>,
+    // Crust and size pairs the kitchen can't make
+    forbidden: Vec<(String, String)>,
+    house: House,
+}
+
+impl Menu {
+    // An empty menu you can add your own items to
+    pub fn new() -> Menu {
+        Menu {
+            items: Vec::new(),
+            forbidden: Vec::new(),
+            house: House {
+                crust: String::from("regular dough"),
+                cheese: String::from("mozzarella"),
+                lunch_size: String::from("small"),
+            },
+        }
+    }
+
+    // The house menu with our current prices
+    pub fn standard() -> Menu {
+        let mut menu = Menu::new();
+
+        // Shorter names for the tags used below
+        use Allergen::*;
+        use Diet::*;
+        let plant_based = [Vegan, Vegetarian, GlutenFree];
+
+        menu.add(MenuItem::new("small", Category::Size, 800));
+        menu.add(MenuItem::new("medium", Category::Size, 1100));
+        menu.add(MenuItem::new("large", Category::Size, 1400));
+
+        menu.add(MenuItem::new("regular dough", Category::Crust, 0)
+            .contains(&[Gluten]).suits(&[Vegan, Vegetarian]));
+        menu.add(MenuItem::new("thin crust", Category::Crust, 0)
+            .contains(&[Gluten]).suits(&[Vegan, Vegetarian]));
+        menu.add(MenuItem::new("whole wheat", Category::Crust, 100)
+            .contains(&[Gluten]).suits(&[Vegan, Vegetarian]));
+        menu.add(MenuItem::new("stuffed crust", Category::Crust, 250)
+            .contains(&[Gluten, Dairy]).suits(&[Vegetarian]));
+        menu.add(MenuItem::new("gluten free crust", Category::Crust, 200)
+            .contains(&[Egg]).suits(&[Vegetarian, GlutenFree]));
+
+        menu.add(MenuItem::new("mozzarella", Category::Cheese, 0)
+            .contains(&[Dairy]).suits(&[Vegetarian, GlutenFree]));
+        menu.add(MenuItem::new("cheddar", Category::Cheese, 50)
+            .contains(&[Dairy]).suits(&[Vegetarian, GlutenFree]));
+        menu.add(MenuItem::new("provolone", Category::Cheese, 75)
+            .contains(&[Dairy]).suits(&[Vegetarian, GlutenFree]));
+        // Made from cashews
+        menu.add(MenuItem::new("vegan cheese", Category::Cheese, 150)
+            .contains(&[Nuts]).suits(&plant_based));
+
+        menu.add(MenuItem::new("veggies", Category::Topping, 150).suits(&plant_based));
+        menu.add(MenuItem::new("pepperoni", Category::Topping, 175).suits(&[GlutenFree]));
+        menu.add(MenuItem::new("mushrooms", Category::Topping, 125).suits(&plant_based));
+        menu.add(MenuItem::new("olives", Category::Topping, 125).suits(&plant_based));
+        // Our sausage uses bread crumbs and soy filler
+        menu.add(MenuItem::new("sausage", Category::Topping, 200).contains(&[Gluten, Soy]));
+        menu.add(MenuItem::new("pineapple", Category::Topping, 150).suits(&plant_based));
+
+        // There isn't enough edge on a small pizza to stuff
+        menu.forbid("stuffed crust", "small");
+
+        menu
+    }
+
+    // Adds an item or replaces the price of one we already sell
+    pub fn add(&mut self, item: MenuItem) {
+        match self.items.iter_mut()
+            .find(|i| i.category == item.category && i.name == item.name) {
+            Some(existing) => *existing = item,
+            None => self.items.push(item),
+        }
+    }
+
+    pub fn house(&self) -> &House {
+        &self.house
+    }
+
+    pub fn set_house(&mut self, house: House) {
+        self.house = house;
+    }
+
+    // Look up an item by name ignoring upper and lower case
+    pub fn find(&self, category: Category, name: &str) -> Option<&MenuItem> {
+        self.items.iter()
+            .find(|i| i.category == category && i.name.eq_ignore_ascii_case(name))
+    }
+
+    // Mark a crust as not available in a size
+    pub fn forbid(&mut self, crust: &str, size: &str) {
+        self.forbidden.push((crust.to_lowercase(), size.to_lowercase()));
+    }
+
+    // Check if the kitchen can make this crust in this size
+    pub fn allows(&self, crust: &str, size: &str) -> bool {
+        !self.forbidden.iter().any(|(c, s)| {
+            c.eq_ignore_ascii_case(crust) && s.eq_ignore_ascii_case(size)
+        })
+    }
+
+    // All items in one category in the order they were added
+    pub fn items(&self, category: Category) -> Vec<&MenuItem> {
+        self.items.iter().filter(|i| i.category == category).collect()
+    }
+
+    // Print the menu grouped by category
+    pub fn print(&self) {
+        for category in Category::ALL {
+            println!("----- {} -----", category.name().to_uppercase());
+            for item in self.items(category) {
+                println!("{:<20}{:>8}  {}", item.name, format_price(item.price), item.tags());
+            }
+        }
+    }
+}
+
+// Turns cents into a price customers can read like $12.50
+pub fn format_price(cents: u32) -> String {
+    format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+// Rates like tax are stored in basis points, hundredths of a percent
+// so 825 means 8.25%. This returns that share of an amount rounded
+// to the nearest cent
+pub fn percent_of(cents: u32, basis_points: u32) -> u32 {
+    ((cents as u64 * basis_points as u64 + 5_000) / 10_000) as u32
+}
+
+// Shows basis points as a percent like 8.25%
+pub fn format_rate(basis_points: u32) -> String {
+    let whole = basis_points / 100;
+    match basis_points % 100 {
+        0 => format!("{}%", whole),
+        part if part % 10 == 0 => format!("{}.{}%", whole, part / 10),
+        part => format!("{}.{:02}%", whole, part),
+    }
+}
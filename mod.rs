@@ -6,53 +6,1123 @@
 // Parent modules can't access private items in child modules
 // but children can always access parent items
 
-mod pizza_order {
+// The menu lives in its own file menu.rs in the restaurant directory
+pub mod menu;
+pub mod order;
+pub mod kitchen;
+pub mod seating;
+pub mod json;
+pub mod receipt;
+pub mod inventory;
+pub mod date;
+pub mod history;
+pub mod api;
+pub mod toml;
+pub mod promotions;
+pub mod dietary;
+pub mod delivery;
+pub mod reservations;
+pub mod loyalty;
+pub mod split;
+pub mod analytics;
+pub mod config;
+pub mod simulation;
+pub mod till;
+pub mod staff;
+pub mod order_text;
+pub mod tickets;
+pub mod nutrition;
+pub mod location;
+pub mod reviews;
+
+pub mod pizza_order {
+
+    // Bring the menu types into scope so we don't have to type
+    // super::menu:: every time
+    use super::json::Json;
+    use super::menu::{Category, Menu, MenuItem};
+    use super::order_text::{self, ParseError};
+    use std::fmt;
+
+    // The most toppings we'll put on one pizza
+    pub const MAX_TOPPINGS: usize = 5;
 
     // To access the struct and the part to make public must both use pub
+    // Each part of the pizza is an item from the menu so it knows
+    // its own price
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Pizza {
-        pub dough: String,
-        pub cheese: String,
-        pub topping: String,
+        pub size: MenuItem,
+        pub crust: MenuItem,
+        pub cheese: MenuItem,
+        pub toppings: Vec<MenuItem>,
+        // Asked for by the customer like extra cheese
+        pub modifiers: Vec<Modifier>,
+    }
+
+    // A change to one part of a pizza. The names are the cheese or
+    // a topping on it
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Modifier {
+        // Twice as much and charged twice
+        Extra(String),
+        Light(String),
+        // Left off and not charged
+        No(String),
+        WellDone,
+    }
+
+    impl Modifier {
+        // The part of the pizza it changes
+        pub fn part(&self) -> Option<&str> {
+            match self {
+                Modifier::Extra(name) | Modifier::Light(name) | Modifier::No(name) => Some(name),
+                Modifier::WellDone => None,
+            }
+        }
+
+        // Reads back what Display writes like "extra olives"
+        pub fn parse(text: &str) -> Option<Modifier> {
+            let text = text.trim();
+            if text.eq_ignore_ascii_case("well done") {
+                return Some(Modifier::WellDone);
+            }
+            let (word, name) = text.split_once(' ')?;
+            let name = String::from(name.trim());
+            match word.to_lowercase().as_str() {
+                "extra" => Some(Modifier::Extra(name)),
+                "light" => Some(Modifier::Light(name)),
+                "no" => Some(Modifier::No(name)),
+                _ => None,
+            }
+        }
+    }
+
+    impl fmt::Display for Modifier {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Modifier::Extra(name) => write!(f, "extra {}", name),
+                Modifier::Light(name) => write!(f, "light {}", name),
+                Modifier::No(name) => write!(f, "no {}", name),
+                Modifier::WellDone => write!(f, "well done"),
+            }
+        }
     }
 
     // Implement functionality for the Pizza struct
     impl Pizza {
-        pub fn lunch(topping: &str) -> Pizza {
-            Pizza {
-                dough: String::from("regular dough"),
-                cheese: String::from("mozzarella"),
-                topping: String::from(topping),
+        // Start building a pizza one part at a time
+        pub fn builder() -> PizzaBuilder {
+            PizzaBuilder::new()
+        }
+
+        // A small regular pizza with mozzarella from the house menu
+        pub fn lunch(topping: &str) -> Result<Pizza, PizzaError> {
+            Pizza::lunch_from(&Menu::standard(), topping)
+        }
+
+        // The lunch pizza the way a menu's house choices make it
+        pub fn lunch_from(menu: &Menu, topping: &str) -> Result<Pizza, PizzaError> {
+            Pizza::builder().size(&menu.house().lunch_size).topping(topping).build_from(menu)
+        }
+
+        // Read a pizza the way a customer says it like
+        // "large thin crust pepperoni and olives, extra cheese"
+        pub fn parse(text: &str) -> Result<Pizza, ParseError> {
+            Pizza::parse_from(&Menu::standard(), text)
+        }
+
+        pub fn parse_from(menu: &Menu, text: &str) -> Result<Pizza, ParseError> {
+            order_text::parse_pizza(menu, text)
+        }
+
+        // The total in cents is the sum of every part of the pizza
+        pub fn price(&self) -> u32 {
+            self.components().iter().map(|c| c.price).sum()
+        }
+
+        // Every menu item that went into the pizza. Parts asked for
+        // extra are in it twice and parts left off aren't in it
+        pub fn components(&self) -> Vec<&MenuItem> {
+            let mut parts = vec![&self.size, &self.crust];
+            let toppings = std::iter::once(&self.cheese).chain(self.toppings.iter());
+            parts.extend(toppings.clone().filter(|t| !self.has(&Modifier::No(t.name.clone()))));
+            parts.extend(toppings.filter(|t| self.has(&Modifier::Extra(t.name.clone()))));
+            parts
+        }
+
+        // Each component named the way a bill shows it. The second
+        // helping of an extra is "extra olives" and a light one is
+        // "light olives"
+        pub fn labelled_components(&self) -> Vec<(String, &MenuItem)> {
+            let mut seen: Vec<&str> = Vec::new();
+            self.components().into_iter().map(|item| {
+                let label = if seen.contains(&item.name.as_str()) {
+                    format!("extra {}", item.name)
+                } else if self.has(&Modifier::Light(item.name.clone())) {
+                    format!("light {}", item.name)
+                } else {
+                    item.name.clone()
+                };
+                seen.push(&item.name);
+                (label, item)
+            }).collect()
+        }
+
+        pub fn has(&self, modifier: &Modifier) -> bool {
+            self.modifiers.contains(modifier)
+        }
+
+        pub fn to_json(&self) -> Json {
+            Json::object(vec![
+                ("description", Json::string(&self.description())),
+                ("size", self.size.to_json()),
+                ("crust", self.crust.to_json()),
+                ("cheese", self.cheese.to_json()),
+                ("toppings", Json::Array(self.toppings.iter().map(|t| t.to_json()).collect())),
+                ("modifiers", Json::Array(self.modifiers.iter()
+                    .map(|m| Json::string(&m.to_string())).collect())),
+                ("price", Json::from(self.price())),
+            ])
+        }
+
+        // Read back a pizza saved with to_json. The prices are the ones
+        // it was sold at, not what the menu says today
+        pub fn from_json(json: &Json) -> Option<Pizza> {
+            let mut toppings = Vec::new();
+            for topping in json.get("toppings")?.as_array()? {
+                toppings.push(MenuItem::from_json(topping)?);
+            }
+            // Pizzas saved before modifiers existed don't have any
+            let mut modifiers = Vec::new();
+            if let Some(saved) = json.get("modifiers") {
+                for modifier in saved.as_array()? {
+                    modifiers.push(Modifier::parse(modifier.as_str()?)?);
+                }
+            }
+            Some(Pizza {
+                size: MenuItem::from_json(json.get("size")?)?,
+                crust: MenuItem::from_json(json.get("crust")?)?,
+                cheese: MenuItem::from_json(json.get("cheese")?)?,
+                toppings,
+                modifiers,
+            })
+        }
+
+        // Something like : large thin crust pizza with mozzarella,
+        // pepperoni and mushrooms (extra mozzarella, well done)
+        pub fn description(&self) -> String {
+            let parts: Vec<&str> = std::iter::once(&self.cheese).chain(self.toppings.iter())
+                .filter(|t| !self.has(&Modifier::No(t.name.clone())))
+                .map(|t| t.name.as_str())
+                .collect();
+            let mut description = format!("{} {} pizza", self.size.name, self.crust.name);
+            match parts.split_last() {
+                Some((last, rest)) if !rest.is_empty() =>
+                    description += &format!(" with {} and {}", rest.join(", "), last),
+                Some((last, _)) => description += &format!(" with {}", last),
+                None => {}
+            }
+            if !self.modifiers.is_empty() {
+                let modifiers: Vec<String> = self.modifiers.iter().map(|m| m.to_string()).collect();
+                description += &format!(" ({})", modifiers.join(", "));
             }
+            description
+        }
+    }
+
+    // Everything that can go wrong when building a pizza
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum PizzaError {
+        MissingSize,
+        NotOnMenu(Category, String),
+        TooManyToppings(usize),
+        DuplicateTopping(String),
+        IncompatibleCrust { crust: String, size: String },
+        // A modifier for something that isn't on the pizza
+        NotOnPizza(String),
+        // Two modifiers for the same part like extra and no cheese
+        ConflictingModifiers(String),
+    }
+
+    // Display defines how the error prints with {}
+    impl fmt::Display for PizzaError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                PizzaError::MissingSize => write!(f, "A pizza needs a size"),
+                PizzaError::NotOnMenu(category, name) =>
+                    write!(f, "{} {} is not on the menu", category.name(), name),
+                PizzaError::TooManyToppings(count) =>
+                    write!(f, "{} toppings is more than the limit of {}",
+                        count, MAX_TOPPINGS),
+                PizzaError::DuplicateTopping(name) =>
+                    write!(f, "{} was added more than once", name),
+                PizzaError::IncompatibleCrust { crust, size } =>
+                    write!(f, "We can't make {} in {}", crust, size),
+                PizzaError::NotOnPizza(name) => write!(f, "There is no {} on the pizza", name),
+                PizzaError::ConflictingModifiers(name) =>
+                    write!(f, "{} was changed more than once", name),
+            }
+        }
+    }
+
+    // Lets PizzaError work with ? in functions returning Box<dyn Error>
+    impl std::error::Error for PizzaError {}
+
+    // A builder collects the choices first and checks them all
+    // at once when build is called
+    pub struct PizzaBuilder {
+        size: Option<String>,
+        crust: Option<String>,
+        cheese: Option<String>,
+        toppings: Vec<String>,
+        modifiers: Vec<Modifier>,
+    }
+
+    impl PizzaBuilder {
+        // Crust and cheese default to the house choices of the menu
+        // the pizza is built from
+        pub fn new() -> PizzaBuilder {
+            PizzaBuilder {
+                size: None,
+                crust: None,
+                cheese: None,
+                toppings: Vec::new(),
+                modifiers: Vec::new(),
+            }
+        }
+
+        // Each method takes self and returns it so calls can be chained
+        pub fn size(mut self, size: &str) -> PizzaBuilder {
+            self.size = Some(String::from(size));
+            self
+        }
+
+        pub fn crust(mut self, crust: &str) -> PizzaBuilder {
+            self.crust = Some(String::from(crust));
+            self
+        }
+
+        pub fn cheese(mut self, cheese: &str) -> PizzaBuilder {
+            self.cheese = Some(String::from(cheese));
+            self
+        }
+
+        pub fn topping(mut self, topping: &str) -> PizzaBuilder {
+            self.toppings.push(String::from(topping));
+            self
+        }
+
+        pub fn modifier(mut self, modifier: Modifier) -> PizzaBuilder {
+            self.modifiers.push(modifier);
+            self
+        }
+
+        // Build using the house menu
+        pub fn build(self) -> Result<Pizza, PizzaError> {
+            self.build_from(&Menu::standard())
+        }
+
+        // Build using any menu, checking every choice against it
+        pub fn build_from(self, menu: &Menu) -> Result<Pizza, PizzaError> {
+            let find = |category: Category, name: &str| {
+                menu.find(category, name)
+                    .cloned()
+                    .ok_or_else(|| PizzaError::NotOnMenu(category, String::from(name)))
+            };
+
+            let size = find(Category::Size, self.size.as_deref()
+                .ok_or(PizzaError::MissingSize)?)?;
+            let house = menu.house();
+            let crust = find(Category::Crust, self.crust.as_deref().unwrap_or(&house.crust))?;
+            let cheese = find(Category::Cheese, self.cheese.as_deref().unwrap_or(&house.cheese))?;
+
+            if !menu.allows(&crust.name, &size.name) {
+                return Err(PizzaError::IncompatibleCrust {
+                    crust: crust.name,
+                    size: size.name,
+                });
+            }
+
+            if self.toppings.len() > MAX_TOPPINGS {
+                return Err(PizzaError::TooManyToppings(self.toppings.len()));
+            }
+
+            let mut toppings: Vec<MenuItem> = Vec::new();
+            for name in &self.toppings {
+                let topping = find(Category::Topping, name)?;
+                if toppings.iter().any(|t| t.name == topping.name) {
+                    return Err(PizzaError::DuplicateTopping(topping.name));
+                }
+                toppings.push(topping);
+            }
+
+            // Modifiers are stored with the menu's spelling of the part
+            let mut modifiers: Vec<Modifier> = Vec::new();
+            for modifier in self.modifiers {
+                let modifier = match modifier.part() {
+                    Some(name) => {
+                        let part = std::iter::once(&cheese).chain(toppings.iter())
+                            .find(|t| t.name.eq_ignore_ascii_case(name))
+                            .ok_or_else(|| PizzaError::NotOnPizza(String::from(name)))?
+                            .name.clone();
+                        match modifier {
+                            Modifier::Extra(_) => Modifier::Extra(part),
+                            Modifier::Light(_) => Modifier::Light(part),
+                            _ => Modifier::No(part),
+                        }
+                    }
+                    None => modifier,
+                };
+                // Asking for well done twice is still just well done
+                if modifiers.contains(&modifier) && modifier.part().is_none() {
+                    continue;
+                }
+                if let Some(part) = modifier.part() {
+                    if modifiers.iter().any(|m| m.part() == Some(part)) {
+                        return Err(PizzaError::ConflictingModifiers(String::from(part)));
+                    }
+                }
+                modifiers.push(modifier);
+            }
+
+            Ok(Pizza { size, crust, cheese, toppings, modifiers })
         }
     }
 
     // help_customer is public so functions can call it
     pub mod help_customer {
-        // This function is private
-        fn seat_at_table() {
-            println!("Customer seated at table");
+        use crate::restaurant::config::RestaurantConfig;
+        use crate::restaurant::date;
+        use crate::restaurant::delivery::{self, Address, Dispatch, ZoneTable};
+        use crate::restaurant::dietary;
+        use crate::restaurant::history::OrderStore;
+        use crate::restaurant::kitchen::Kitchen;
+        use crate::restaurant::loyalty::{Loyalty, Perk};
+        use crate::restaurant::menu::format_price;
+        use crate::restaurant::order::{self, Order};
+        use crate::restaurant::promotions::Promotions;
+        use crate::restaurant::reservations::{ReservationBook, ReservationError};
+        use crate::restaurant::receipt::{Receipt, ReceiptConfig, Tip};
+        use crate::restaurant::reviews::ReviewBook;
+        use crate::restaurant::split::{self, PayerReceipt, Split, SplitError};
+        use crate::restaurant::staff::{Role, Roster};
+        use crate::restaurant::till::{Reason, Till};
+        use std::time::SystemTime;
+
+        // pub use re-exports the seating types so anyone using
+        // help_customer can work with the floor too
+        pub use crate::restaurant::seating::{Floor, Party, Seating, SeatingError};
+
+        // Put a party at the best free table or on the waitlist
+        pub fn seat_at_table(floor: &mut Floor, book: &mut ReservationBook, party: Party)
+                             -> Result<Seating, SeatingError> {
+            // Tables for parties due soon are kept from walk ins
+            update_holds(book, floor, SystemTime::now());
+            let name = party.name.clone();
+            let seating = floor.seat(party)?;
+            match seating {
+                Seating::Seated(table) => println!("{} seated at table {}", name, table),
+                Seating::Waitlisted(place) => println!("{} is number {} on the waitlist", name, place),
+            }
+            Ok(seating)
+        }
+
+        // The party at a table leaves and anyone waiting who fits
+        // is seated. Returns who was seated from the waitlist
+        pub fn leave_table(floor: &mut Floor, table: u32) -> Result<Vec<(Party, u32)>, SeatingError> {
+            let (left, seated) = floor.release(table)?;
+            println!("{} left table {}", left.name, table);
+            for (party, table) in &seated {
+                println!("{} seated at table {} from the waitlist", party.name, table);
+            }
+            Ok(seated)
+        }
+
+        // Give away the tables of parties that didn't show up and keep
+        // tables for parties due soon away from walk ins
+        pub fn update_holds(book: &mut ReservationBook, floor: &mut Floor, at: SystemTime) {
+            for missed in book.mark_no_shows(at) {
+                println!("{} didn't show up so table {} is free", missed.party.name, missed.table);
+            }
+            floor.hold_tables(&book.held_tables(at));
+        }
+
+        // A party arrives for their reservation
+        pub fn check_in(book: &mut ReservationBook, floor: &mut Floor, id: u32)
+                        -> Result<u32, ReservationError> {
+            let table = book.check_in(id, floor)?;
+            if let Some(reservation) = book.get(id) {
+                println!("{} seated at reserved table {}", reservation.party.name, table);
+            }
+            Ok(table)
         }
 
         // Making help_customer public doesn't make this child
         // function public so we must also make it public
-        pub fn take_order() {
-            seat_at_table();
+        pub fn take_order(kitchen: &Kitchen, config: &RestaurantConfig, roster: &mut Roster,
+                          floor: &mut Floor, book: &mut ReservationBook, party: Party) {
+            if !is_open(config) {
+                return;
+            }
+            let customer = party.name.clone();
+            let needs = party.needs.clone();
+            let table = match seat_at_table(floor, book, party) {
+                Ok(Seating::Seated(table)) => table,
+                // They'll order once they have a table
+                Ok(Seating::Waitlisted(_)) => return,
+                Err(e) => {
+                    println!("Sorry : {}", e);
+                    return;
+                }
+            };
 
             // super allows me to access pizza in the parent scope
-            let cust_pizza: super::Pizza =
-                super::Pizza::lunch("veggies");
+            let cust_pizza = match super::Pizza::lunch_from(&config.menu, "veggies") {
+                Ok(pizza) => pizza,
+                Err(e) => {
+                    println!("Sorry : {}", e);
+                    give_back_table(floor, table);
+                    return;
+                }
+            };
 
-            serve_customer(cust_pizza);
+            // The kitchen cooks the order on its own threads and
+            // calls serve_customer when it is ready
+            let mut order = Order::new(order::next_id(), vec![cust_pizza]);
+            order.table = Some(table);
+            order.customer = Some(customer);
+            order.needs = needs;
+            if !can_accept(kitchen, &order) {
+                give_back_table(floor, table);
+                return;
+            }
+            let id = order.id;
+
+            // The host who seated them and a server look after the
+            // table. When nobody is on shift the manager does it
+            // They are picked before the kitchen starts so they are
+            // saved with the order when it is served and let go again
+            // if the kitchen doesn't take it
+            let roles = [Role::Host, Role::Server];
+            for role in roles {
+                match roster.assign(id, role, SystemTime::now()) {
+                    Ok(employee) => println!("{} is the {} for order {}", employee.name, role.name(), id),
+                    Err(e) => println!("{} so the manager is the {} for order {}", e, role.name(), id),
+                }
+            }
+            if let Err(e) = kitchen.submit(order) {
+                println!("{}", e);
+                for role in roles {
+                    roster.unassign(id, role);
+                }
+                give_back_table(floor, table);
+            }
         }
 
-        fn serve_customer(cust_pizza: super::Pizza){
-            println!("The customer is served a regular pizza with {}", cust_pizza.topping);
+        // Orders are only taken while the store is open
+        fn is_open(config: &RestaurantConfig) -> bool {
+            if config.is_open(SystemTime::now()) {
+                return true;
+            }
+            println!("Sorry, {} is closed. We are open from {} to {}", config.name,
+                date::format_time_of_day(config.opens), date::format_time_of_day(config.closes));
+            false
+        }
+
+        // A party we couldn't take an order from leaves so the table
+        // goes to the next party waiting
+        fn give_back_table(floor: &mut Floor, table: u32) {
+            if let Err(e) = leave_table(floor, table) {
+                println!("{}", e);
+            }
         }
 
+        // Take an order over the phone to be driven to the customer
+        // The pizza is typed the way the customer says it
+        // Returns the order number if the kitchen accepted it
+        pub fn take_delivery(kitchen: &Kitchen, config: &RestaurantConfig, zones: &ZoneTable,
+                             dispatch: &mut Dispatch, customer: &str, address: &str,
+                             said: &str) -> Option<u32> {
+            if !is_open(config) {
+                return None;
+            }
+            let cust_pizza = match super::Pizza::parse_from(&config.menu, said) {
+                Ok(pizza) => pizza,
+                Err(e) => {
+                    println!("Sorry : {}", e);
+                    return None;
+                }
+            };
+
+            let quote = match Address::parse(address).and_then(|a| zones.quote(&a)) {
+                Ok(quote) => quote,
+                Err(e) => {
+                    println!("Sorry : {}", e);
+                    return None;
+                }
+            };
+            println!("Delivering to {}", delivery::describe(&quote));
+
+            let mut order = Order::new(order::next_id(), vec![cust_pizza]);
+            order.customer = Some(String::from(customer));
+            order.delivery = Some(quote);
+            if !can_accept(kitchen, &order) {
+                return None;
+            }
+            // Booked before the kitchen starts so a driver can be
+            // found the moment it is ready
+            if let Err(e) = dispatch.book(&order) {
+                println!("{}", e);
+                return None;
+            }
+            let id = order.id;
+
+            // The host answers the phone. The kitchen's roster is used
+            // so the cooks and the host share one
+            if let Some(roster) = kitchen.roster() {
+                let mut roster = roster.lock().unwrap();
+                match roster.assign(id, Role::Host, SystemTime::now()) {
+                    Ok(employee) => println!("{} took delivery order {}", employee.name, id),
+                    Err(e) => println!("{} so the manager took delivery order {}", e, id),
+                }
+            }
+            match kitchen.submit(order) {
+                Ok(_) => Some(id),
+                Err(e) => {
+                    println!("{}", e);
+                    if let Some(roster) = kitchen.roster() {
+                        roster.lock().unwrap().unassign(id, Role::Host);
+                    }
+                    // The kitchen never got it so no driver should wait for it
+                    if let Err(e) = dispatch.cancel(id) {
+                        println!("{}", e);
+                    }
+                    None
+                }
+            }
+        }
+
+        // Checks every order goes through before it is cooked
+        fn can_accept(kitchen: &Kitchen, order: &Order) -> bool {
+            // Never cook something the customer is allergic to. Diet
+            // conflicts are passed on so the server can double check
+            match dietary::screen(order) {
+                Ok(warnings) => for warning in warnings {
+                    println!("Check with the customer : {}", warning);
+                },
+                Err(e) => {
+                    println!("Sorry : {}", e);
+                    return false;
+                }
+            }
+            if let Err(e) = kitchen.can_make(order) {
+                println!("Sorry we can't make that : {}", e);
+                return false;
+            }
+            true
+        }
+
+        // Public so the kitchen can be opened with it
+        pub fn serve_customer(order: &mut Order) {
+            if let Err(e) = order.serve() {
+                println!("Order {} can't be served : {}", order.id, e);
+                return;
+            }
+            let table = order.table.map_or(String::new(), |t| format!(" at table {}", t));
+            for cust_pizza in &order.pizzas {
+                println!("The customer{} is served a {} for {}", table,
+                    cust_pizza.description(),
+                    format_price(cust_pizza.price()));
+            }
+        }
+
+        // Delivery orders are served when they leave with a driver
+        pub fn send_out(order: &mut Order, dispatch: &mut Dispatch, roster: &mut Roster) {
+            if let Err(e) = order.serve() {
+                println!("Order {} can't be sent out : {}", order.id, e);
+                return;
+            }
+            match dispatch.assign(order.id) {
+                Ok(driver) => {
+                    println!("{} is taking order {} to {}", driver, order.id,
+                        order.delivery.as_ref().map_or(String::new(), |d| d.address.to_string()));
+                    assign_driver(roster, order.id, &driver);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+
+        // The driver dispatch sent is the driver for the order
+        fn assign_driver(roster: &mut Roster, order_id: u32, driver: &str) {
+            let Some(id) = roster.find(driver).map(|e| e.id) else {
+                println!("{} isn't on the roster", driver);
+                return;
+            };
+            if let Err(e) = roster.assign_to(order_id, Role::Driver, id) {
+                println!("{}", e);
+            }
+        }
+
+        // The driver dropped the order off and is free again
+        pub fn deliver(dispatch: &mut Dispatch, roster: &mut Roster, order_id: u32) {
+            let sent = match dispatch.complete(order_id) {
+                Ok(sent) => sent,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            if let Some(delivery) = dispatch.delivery(order_id) {
+                let late = if delivery.on_time() == Some(true) { "on time" } else { "late" };
+                println!("Order {} was delivered {} by {}", order_id, late,
+                    delivery.driver.as_deref().unwrap_or("nobody"));
+            }
+            for (id, driver) in sent {
+                println!("{} is taking order {} that was waiting", driver, id);
+                assign_driver(roster, id, &driver);
+            }
+        }
+
+        // Any promotions that apply come off the bill, then any
+        // loyalty points the customer is spending. Members earn
+        // points on what is left to pay
+        pub fn bring_bill(order: &Order, config: &ReceiptConfig, promotions: &mut Promotions,
+                          loyalty: &mut Loyalty) -> Receipt {
+            // Coupons that couldn't be saved aren't taken off but
+            // every other promotion still is
+            let (mut breakdown, unsaved) = promotions.apply(order, SystemTime::now());
+            if order.points > 0 {
+                match loyalty.redeem(order, breakdown.total) {
+                    Ok(redeemed) => {
+                        breakdown.total -= redeemed.amount;
+                        breakdown.applied.push(redeemed);
+                    }
+                    Err(e) => println!("Points not used : {}", e),
+                }
+            }
+            for promotion in &breakdown.applied {
+                println!("{} : {}", promotion.name, promotion.explanation);
+            }
+
+            let mut receipt = Receipt::discounted(order, config, Tip::None, Some(&breakdown));
+            if let Some(e) = &unsaved {
+                receipt = receipt.with_note(&format!("Coupons weren't taken off : {}", e));
+            }
+            if loyalty.has_perk(order, Perk::FreeDelivery) {
+                receipt = receipt.with_delivery_fee(0);
+            }
+            println!("{}", receipt.to_text());
+
+            if let Some(customer) = order.customer.as_deref().filter(|c| loyalty.is_member(c)) {
+                match loyalty.earn(order, breakdown.total) {
+                    Ok(points) => println!("{} earned {} points and has {}", customer, points,
+                        loyalty.balance(customer)),
+                    Err(e) => println!("No points : {}", e),
+                }
+            }
+            receipt
+        }
+
+        // The customer pays and the till keeps a record of it
+        // The order remembers what was paid for the sales report
+        pub fn take_payment(till: &mut Till, order: &mut Order, receipt: &Receipt) {
+            match till.pay(receipt) {
+                Ok(_) => {
+                    order.paid = Some(receipt.total);
+                    println!("Order {} paid {}", receipt.order_id, format_price(receipt.total));
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+
+        // Change the pizzas on an order the kitchen hasn't started
+        // The till notes how much the change added or took off
+        pub fn change_order<F>(kitchen: &Kitchen, till: &mut Till, id: u32, note: &str, change: F)
+        where
+            F: FnOnce(&mut Order) -> Result<(), order::OrderError>,
+        {
+            let before = match kitchen.order(id) {
+                Some(order) => order.total(),
+                None => {
+                    println!("There is no order {}", id);
+                    return;
+                }
+            };
+            match kitchen.modify(id, change) {
+                Ok(order) => {
+                    if let Err(e) = till.modified(before, &order, note) {
+                        println!("{}", e);
+                    }
+                    println!("Order {} now costs {}", id, format_price(order.total()));
+                }
+                Err(e) => println!("Sorry : {}", e),
+            }
+        }
+
+        // A manager takes an order off the books before it is paid
+        pub fn void_order(kitchen: &Kitchen, till: &mut Till, store: &mut OrderStore, id: u32,
+                          reason: Reason) {
+            let Some(mut order) = kitchen.order(id) else {
+                println!("There is no order {}", id);
+                return;
+            };
+            if till.is_paid(id) {
+                println!("Order {} is already paid, give a refund instead", id);
+                return;
+            }
+            // The till goes first so a void that can't be recorded
+            // leaves the order cooking. Orders still cooking are then
+            // stopped and served ones come off the bill. The history
+            // gets the voided copy so it isn't counted as a sale
+            if let Err(e) = till.void(&mut order, reason, "") {
+                println!("Sorry : {}", e);
+                return;
+            }
+            if let Err(e) = kitchen.void(id) {
+                println!("Sorry : {}", e);
+                // The kitchen kept the order so it goes back on the books
+                if let Err(e) = till.reinstate(id, &e.to_string()) {
+                    println!("Order {} is still voided in the till : {}", id, e);
+                }
+                return;
+            }
+            println!("Order {} was voided ({})", id, reason.code());
+            if let Err(e) = store.append(&order) {
+                println!("{}", e);
+            }
+        }
+
+        // Give back some or all (None) of what was paid
+        pub fn refund(till: &mut Till, order_id: u32, amount: Option<u32>, reason: Reason) {
+            match till.refund(order_id, amount, reason, "") {
+                Ok(entry) => println!("Refunded {} on order {} ({})",
+                    format_price(entry.amount.unsigned_abs() as u32), order_id, reason.code()),
+                Err(e) => println!("Can't refund : {}", e),
+            }
+        }
+
+        // Everyone at the table pays their own part of the bill
+        pub fn split_bill(receipt: &Receipt, payers: &[&str], how: &Split)
+                          -> Result<Vec<PayerReceipt>, SplitError> {
+            let bills = split::split(receipt, payers, how)?;
+            for bill in &bills {
+                println!("{}", bill.to_text());
+            }
+            Ok(bills)
+        }
+
+        // The customer rates the order and whoever worked it gets
+        // the same rating
+        pub fn leave_review(reviews: &mut ReviewBook, order: &Order, stars: u32, comment: &str) {
+            match reviews.add(order, stars, comment) {
+                Ok(review) => println!("{}", review),
+                Err(e) => println!("Can't review : {}", e),
+            }
+        }
     }
 }
 
 // This is the public function that allows our other file access
 pub fn order_food() {
-    crate::restaurant::pizza_order::help_customer::take_order();
+    use crate::restaurant::pizza_order::help_customer::{
+        bring_bill, deliver, leave_review, refund, send_out, serve_customer, split_bill,
+        take_delivery, take_order, take_payment, Party,
+    };
+    use crate::restaurant::config::RestaurantConfig;
+    use crate::restaurant::delivery::{Dispatch, ZoneTable};
+    use crate::restaurant::dietary::{Diet, DietaryNeeds};
+    use crate::restaurant::location::{Franchise, Location};
+    use crate::restaurant::promotions::Promotions;
+    use crate::restaurant::receipt::{Receipt, Tip};
+    use crate::restaurant::reviews::ReviewBook;
+    use crate::restaurant::split::Split;
+    use crate::restaurant::staff::Role;
+    use crate::restaurant::tickets::{TicketFormat, TicketPrinter};
+    use crate::restaurant::till::{Reason, Till};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
+    // The menu, tax, hours and tables. Without the file we use the
+    // standard settings
+    let config = match RestaurantConfig::load(Path::new("restaurant.toml")) {
+        Ok(config) => config,
+        Err(e) if e.is_missing() => RestaurantConfig::standard(),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let receipt_config = config.receipt.clone();
+
+    // Orders from earlier runs are loaded so we keep adding to them
+    let store = match history::OrderStore::open("orders.jsonl") {
+        Ok(store) => Arc::new(Mutex::new(store)),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    // Today's specials. Without the file there just aren't any
+    let mut promotions = match Promotions::load(Path::new("promotions.toml")) {
+        Ok(promotions) => promotions,
+        Err(e) if e.is_missing() => Promotions::new(),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if let Err(e) = promotions.open_redemptions("coupons.jsonl") {
+        println!("{}", e);
+        return;
+    }
+    let promotions = Mutex::new(promotions);
+
+    // Bob is a loyalty member who is spending points today
+    let mut loyalty = match loyalty::Loyalty::open("loyalty.jsonl") {
+        Ok(loyalty) => loyalty,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if !loyalty.is_member("Bob Smith") {
+        if let Err(e) = loyalty.join("Bob Smith") {
+            println!("{}", e);
+        }
+    }
+    let loyalty = Arc::new(Mutex::new(loyalty));
+    let members = loyalty.clone();
+
+    // Every payment and refund is kept so the drawer always adds up
+    let till = match Till::open("till.jsonl") {
+        Ok(till) => till,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    // What customers thought of their orders
+    let mut reviews = match ReviewBook::open("reviews.jsonl") {
+        Ok(reviews) => reviews,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    // This store and a second one at the airport. Each has its own
+    // stock, staff and till and their sales are rolled up at the end
+    let mut airport = RestaurantConfig::standard();
+    airport.name = String::from("Rusty Pizza Airport");
+    airport.location = String::from("airport");
+
+    // Orders are only taken while a store is open. The demo can run
+    // at any hour so a closed store stays open all day for it
+    let mut config = config;
+    for store in [&mut config, &mut airport] {
+        if !store.is_open(SystemTime::now()) {
+            println!("{} is closed right now so the demo keeps it open all day", store.name);
+            store.opens = 0;
+            store.closes = 24 * 60;
+        }
+    }
+    let mut chain = Franchise::new();
+    for location in [Location::new(config).with_till(till), Location::new(airport)] {
+        if let Err(e) = chain.add(location) {
+            println!("{}", e);
+            return;
+        }
+    }
+    let here = &chain.locations()[0];
+    let config = &here.config;
+    let till = here.till.clone();
+    let drawer = till.clone();
+
+    // Open a kitchen with 2 cooks, take an order and then close
+    // up once everything in the queue has been served
+    let history = store.clone();
+
+    // Everyone on shift clocks in. Drivers on shift take the
+    // deliveries and Dana is called in when nobody is
+    let roster = here.roster.clone();
+    let now = SystemTime::now();
+    let mut dispatch = Dispatch::new();
+    {
+        let mut roster = roster.lock().unwrap();
+        for role in Role::ALL {
+            let on_shift: Vec<(u32, String)> = roster.on_shift(role, now).iter()
+                .map(|e| (e.id, e.name.clone()))
+                .collect();
+            for (id, name) in on_shift {
+                if let Err(e) = roster.clock_in(id, now) {
+                    println!("{}", e);
+                }
+                if role == Role::Driver {
+                    dispatch.add_driver(&name);
+                }
+            }
+        }
+        if roster.on_shift(Role::Driver, now).is_empty() {
+            println!("No driver is on shift so Dana is called in");
+            dispatch.add_driver("Dana");
+        }
+    }
+    let dispatch = Arc::new(Mutex::new(dispatch));
+    let drivers = dispatch.clone();
+    // Shared with the kitchen so the cooks and drivers who made and
+    // took each order are noted
+    let staff = roster.clone();
+
+    // Keep the receipts so a bill can be split after the meal
+    let receipts = Arc::new(Mutex::new(Vec::new()));
+    let bills = receipts.clone();
+
+    // The store's kitchen cooks from its stock, gives orders to its
+    // cooks and tags every order with the store's code
+    let kitchen = here.open_kitchen(2, Duration::from_millis(10),
+        move |order| {
+            if order.delivery.is_some() {
+                send_out(order, &mut drivers.lock().unwrap(), &mut staff.lock().unwrap());
+            } else {
+                serve_customer(order);
+            }
+            let receipt = bring_bill(order, &receipt_config, &mut promotions.lock().unwrap(),
+                &mut members.lock().unwrap());
+            take_payment(&mut drawer.lock().unwrap(), order, &receipt);
+            order.staff = staff.lock().unwrap().names(order.id);
+            bills.lock().unwrap().push(receipt);
+            if let Err(e) = history.lock().unwrap().append(order) {
+                println!("{}", e);
+            }
+        });
+    // There is no thermal printer yet so tickets go in a file
+    kitchen.attach_printer(TicketPrinter::new("tickets.txt", TicketFormat::Text));
+    // Deliveries the kitchen can't make are called off
+    kitchen.attach_dispatch(dispatch.clone());
+
+    // Sally books the big table for tomorrow night. Walk ins today
+    // only get tables nobody has reserved
+    let mut floor = config.floor();
+    let mut book = config.reservation_book();
+    let tomorrow = date::Date::today().next();
+    match date::parse_time_of_day("19:00") {
+        Some(dinner) => {
+            match book.book(&floor, Party::new("Sally Jones", 6), tomorrow, dinner) {
+                Ok(reservation) => println!("Sally has table {} at 19:00", reservation.table),
+                Err(e) => println!("Sorry : {}", e),
+            }
+            if let Err(e) = book.book_table(&floor, Party::new("Paul Green", 5), 8, tomorrow,
+                dinner) {
+                println!("Sorry Paul : {}", e);
+            }
+            println!("{}", book.day_sheet(tomorrow));
+        }
+        None => println!("19:00 isn't a time of day"),
+    }
+
+    let bob = Party::new("Bob Smith", 2)
+        .with_needs(DietaryNeeds::new().diet(Diet::Vegetarian));
+    take_order(&kitchen, config, &mut roster.lock().unwrap(), &mut floor, &mut book, bob);
+
+    // Bob calls again later and wants a pizza sent home. The first
+    // time it is typed in a hurry and the typos are caught
+    let zones = ZoneTable::standard();
+    let mut delivery = None;
+    for said in ["large thn crust peperoni and olives, extra cheese",
+                 "large thin crust pepperoni and olives, extra cheese"] {
+        delivery = take_delivery(&kitchen, config, &zones, &mut dispatch.lock().unwrap(),
+            "Bob Smith", "505 Main St", said);
+        if delivery.is_some() {
+            break;
+        }
+    }
+
+    // Bob asks how many calories he just ordered
+    if let Some(order) = delivery.and_then(|id| kitchen.order(id)) {
+        let stock = kitchen.inventory();
+        for pizza in &order.pizzas {
+            match config.nutrition.pizza(&stock.lock().unwrap(), pizza) {
+                Ok(label) => println!("{}", label.to_text()),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+    kitchen.shutdown();
+    if let Some(id) = delivery {
+        deliver(&mut dispatch.lock().unwrap(), &mut roster.lock().unwrap(), id);
+    }
+
+    // Bob and Jane go halves on the meal they ate in
+    let dine_in = receipts.lock().unwrap().iter()
+        .find(|r| Some(r.order_id) != delivery).cloned();
+    if let Some(receipt) = dine_in {
+        if let Err(e) = split_bill(&receipt, &["Bob Smith", "Jane Smith"], &Split::Evenly) {
+            println!("Can't split the bill : {}", e);
+        }
+
+        // Bob's crust was burnt so he gets $2 back. Asking for more
+        // than was paid is refused
+        let mut till = till.lock().unwrap();
+        refund(&mut till, receipt.order_id, Some(200), Reason::Quality);
+        refund(&mut till, receipt.order_id, Some(receipt.total), Reason::Quality);
+        println!("{}", till.day_sheet(date::Date::today()));
+
+        // Bob still rates the meal. He can't rate it twice
+        if let Some(order) = store.lock().unwrap().get(receipt.order_id) {
+            leave_review(&mut reviews, order, 3, "Burnt crust but they made it right");
+            leave_review(&mut reviews, order, 5, "");
+        }
+    }
+
+    // Jane eats at the airport store. It cooks from its own stock and
+    // takes the money in its own till
+    let airport = &chain.locations()[1];
+    let airport_till = airport.till.clone();
+    let airport_staff = airport.roster.clone();
+    let airport_history = store.clone();
+    let airport_receipt = airport.config.receipt.clone();
+    let airport_kitchen = airport.open_kitchen(1, Duration::from_millis(10), move |order| {
+        serve_customer(order);
+        let receipt = Receipt::for_order(order, &airport_receipt, Tip::None);
+        take_payment(&mut airport_till.lock().unwrap(), order, &receipt);
+        order.staff = airport_staff.lock().unwrap().names(order.id);
+        if let Err(e) = airport_history.lock().unwrap().append(order) {
+            println!("{}", e);
+        }
+    });
+    take_order(&airport_kitchen, &airport.config, &mut airport.roster.lock().unwrap(),
+        &mut airport.config.floor(), &mut airport.config.reservation_book(),
+        Party::new("Jane Smith", 1));
+    airport_kitchen.shutdown();
+
+    let sold = store.lock().unwrap().sold_on(date::Date::today()).len();
+    println!("Orders served today : {}", sold);
+
+    // How the restaurant has done over every run so far
+    let report = analytics::Report::with_refunds(store.lock().unwrap().all(),
+        &till.lock().unwrap());
+    println!("{}", report.to_text());
+    if let Err(e) = report.save_csv("sales.csv") {
+        println!("Couldn't save the sales report : {}", e);
+    }
+    // The same sales split by store with each store's refunds. Orders
+    // from before stores had codes show up as (none)
+    let rollup = chain.rollup(store.lock().unwrap().all());
+    println!("{}", rollup.to_text());
+    println!("{}", loyalty.lock().unwrap().statement("Bob Smith"));
+    println!("{}", reviews.report(date::Date::today(), 7));
+
+    // Everyone goes home and the manager checks today's hours
+    let mut roster = roster.lock().unwrap();
+    let ids: Vec<u32> = roster.time_cards().iter().map(|c| c.employee_id).collect();
+    for id in ids {
+        if let Err(e) = roster.clock_out(id, SystemTime::now()) {
+            println!("{}", e);
+        }
+    }
+    println!("{}", roster.hours_report(date::Date::today(), date::Date::today()));
 }
@@ -0,0 +1,323 @@
+// Calories and macronutrients for everything we make. Each
+// ingredient in the walk in cooler has its nutrition per 100g and
+// the inventory recipes say how many grams of each go into every
+// part of a pizza, so the numbers always match what the cooks use
+// Extras count twice and parts left off don't count
+
+// Calories are kcal and protein, carbs and fat are grams
+
+use super::inventory::{Inventory, InventoryError};
+use super::json::Json;
+use super::menu::{Category, Menu, MenuItem};
+use super::pizza_order::Pizza;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::AddAssign;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NutritionError {
+    Inventory(InventoryError),
+    // An ingredient nobody entered nutrition for
+    MissingIngredient(String),
+    // A size we don't know how many slices it is cut into
+    MissingSlices(String),
+}
+
+impl fmt::Display for NutritionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NutritionError::Inventory(e) => write!(f, "{}", e),
+            NutritionError::MissingIngredient(name) =>
+                write!(f, "There is no nutrition information for {}", name),
+            NutritionError::MissingSlices(size) =>
+                write!(f, "Nobody said how many slices a {} pizza has", size),
+        }
+    }
+}
+
+impl std::error::Error for NutritionError {}
+
+impl From<InventoryError> for NutritionError {
+    fn from(e: InventoryError) -> NutritionError {
+        NutritionError::Inventory(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Nutrition {
+    pub calories: f64,
+    pub protein: f64,
+    pub carbs: f64,
+    pub fat: f64,
+}
+
+impl Nutrition {
+    pub fn new(calories: f64, protein: f64, carbs: f64, fat: f64) -> Nutrition {
+        Nutrition { calories, protein, carbs, fat }
+    }
+
+    // The amount in some grams of something measured per 100g
+    pub fn for_grams(&self, grams: u32) -> Nutrition {
+        self.times(grams as f64 / 100.0)
+    }
+
+    pub fn times(&self, by: f64) -> Nutrition {
+        Nutrition::new(self.calories * by, self.protein * by, self.carbs * by, self.fat * by)
+    }
+
+    // Json only has whole numbers so this is rounded like the
+    // label on packaged food
+    pub fn to_json(self) -> Json {
+        let whole = |amount: f64| Json::from(amount.round() as i64);
+        Json::object(vec![
+            ("calories", whole(self.calories)),
+            ("protein", whole(self.protein)),
+            ("carbs", whole(self.carbs)),
+            ("fat", whole(self.fat)),
+        ])
+    }
+}
+
+impl AddAssign for Nutrition {
+    fn add_assign(&mut self, other: Nutrition) {
+        self.calories += other.calories;
+        self.protein += other.protein;
+        self.carbs += other.carbs;
+        self.fat += other.fat;
+    }
+}
+
+// Everything about one pizza
+#[derive(Debug, Clone, PartialEq)]
+pub struct PizzaNutrition {
+    pub description: String,
+    pub total: Nutrition,
+    pub slices: u32,
+    // Each part of the pizza in the order it was made. The size is
+    // the sauce every pizza gets
+    pub parts: Vec<(String, Nutrition)>,
+}
+
+impl PizzaNutrition {
+    pub fn per_slice(&self) -> Nutrition {
+        self.total.times(1.0 / self.slices as f64)
+    }
+
+    // A label like the ones on packaged food
+    pub fn to_text(&self) -> String {
+        let slice = self.per_slice();
+        let mut lines = vec![
+            self.description.clone(),
+            format!("{:<16}{:>8}{:>14}", "", "Pizza", format!("Slice (1/{})", self.slices)),
+            format!("{:<16}{:>8.0}{:>14.0}", "Calories", self.total.calories, slice.calories),
+        ];
+        for (label, whole, part) in [("Protein", self.total.protein, slice.protein),
+                                     ("Carbs", self.total.carbs, slice.carbs),
+                                     ("Fat", self.total.fat, slice.fat)] {
+            lines.push(format!("{:<16}{:>7.0}g{:>13.1}g", label, whole, part));
+        }
+        lines.push(String::from("Calories from"));
+        for (name, nutrition) in &self.parts {
+            lines.push(format!("  {:<22}{:>6.0}", name, nutrition.calories));
+        }
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("description", Json::string(&self.description)),
+            ("slices", Json::from(self.slices)),
+            ("total", self.total.to_json()),
+            ("per_slice", self.per_slice().to_json()),
+            ("parts", Json::Array(self.parts.iter().map(|(name, nutrition)| Json::object(vec![
+                ("name", Json::string(name)),
+                ("nutrition", nutrition.to_json()),
+            ])).collect())),
+        ])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NutritionTable {
+    // Keyed by inventory ingredient
+    per_100g: HashMap<String, Nutrition>,
+    // How many slices each size is cut into
+    slices: HashMap<String, u32>,
+}
+
+impl NutritionTable {
+    pub fn new() -> NutritionTable {
+        NutritionTable {
+            per_100g: HashMap::new(),
+            slices: HashMap::new(),
+        }
+    }
+
+    // Every ingredient in the standard inventory
+    pub fn standard() -> NutritionTable {
+        let mut table = NutritionTable::new();
+        table.set_slices("small", 6);
+        table.set_slices("medium", 8);
+        table.set_slices("large", 10);
+
+        let ingredients = [
+            ("tomato sauce", 24.0, 1.2, 5.3, 0.2),
+            ("dough", 260.0, 8.0, 50.0, 2.5),
+            ("wheat dough", 240.0, 9.5, 45.0, 2.5),
+            ("gluten free dough", 250.0, 3.0, 48.0, 6.0),
+            ("mozzarella", 280.0, 28.0, 3.1, 17.0),
+            ("cheddar", 403.0, 25.0, 1.3, 33.0),
+            ("provolone", 351.0, 26.0, 2.1, 27.0),
+            ("vegan cheese", 300.0, 5.0, 20.0, 23.0),
+            ("peppers", 20.0, 0.9, 4.6, 0.2),
+            ("onions", 40.0, 1.1, 9.3, 0.1),
+            ("pepperoni", 494.0, 23.0, 1.2, 44.0),
+            ("mushrooms", 22.0, 3.1, 3.3, 0.3),
+            ("olives", 115.0, 0.8, 6.0, 11.0),
+            ("sausage", 300.0, 17.0, 3.0, 25.0),
+            ("pineapple", 50.0, 0.5, 13.0, 0.1),
+        ];
+        for (ingredient, calories, protein, carbs, fat) in ingredients {
+            table.set_ingredient(ingredient, Nutrition::new(calories, protein, carbs, fat));
+        }
+        table
+    }
+
+    pub fn set_ingredient(&mut self, ingredient: &str, per_100g: Nutrition) {
+        self.per_100g.insert(ingredient.to_lowercase(), per_100g);
+    }
+
+    // Nutrition per 100g if anybody entered it
+    pub fn ingredient(&self, ingredient: &str) -> Option<Nutrition> {
+        self.per_100g.get(&ingredient.to_lowercase()).copied()
+    }
+
+    pub fn set_slices(&mut self, size: &str, slices: u32) {
+        self.slices.insert(size.to_lowercase(), slices.max(1));
+    }
+
+    pub fn slices(&self, size: &str) -> Result<u32, NutritionError> {
+        self.slices.get(&size.to_lowercase()).copied()
+            .ok_or_else(|| NutritionError::MissingSlices(String::from(size)))
+    }
+
+    // What one part adds to a whole pizza of a size
+    pub fn item(&self, inventory: &Inventory, item: &MenuItem, size: &str)
+                -> Result<Nutrition, NutritionError> {
+        let mut total = Nutrition::default();
+        for (ingredient, grams) in inventory.item_requirements(item, size)? {
+            let per_100g = self.per_100g.get(&ingredient.to_lowercase())
+                .ok_or(NutritionError::MissingIngredient(ingredient))?;
+            total += per_100g.for_grams(grams);
+        }
+        Ok(total)
+    }
+
+    pub fn pizza(&self, inventory: &Inventory, pizza: &Pizza) -> Result<PizzaNutrition, NutritionError> {
+        let size = &pizza.size.name;
+        let mut total = Nutrition::default();
+        let mut parts = Vec::new();
+        for (label, item) in pizza.labelled_components() {
+            let nutrition = self.item(inventory, item, size)?;
+            total += nutrition;
+            let name = if item.category == Category::Size { String::from("sauce") } else { label };
+            parts.push((name, nutrition));
+        }
+        Ok(PizzaNutrition {
+            description: pizza.description(),
+            total,
+            slices: self.slices(size)?,
+            parts,
+        })
+    }
+
+    // Calories a part adds to one slice of a size
+    pub fn slice_calories(&self, inventory: &Inventory, item: &MenuItem, size: &str)
+                          -> Result<f64, NutritionError> {
+        Ok(self.item(inventory, item, size)?.calories / self.slices(size)? as f64)
+    }
+
+    // The sizes an item can be had in. A size is only itself and
+    // some crusts don't come in every size
+    pub fn sizes_for<'a>(menu: &'a Menu, item: &MenuItem) -> Vec<&'a MenuItem> {
+        menu.items(Category::Size).into_iter()
+            .filter(|size| match item.category {
+                Category::Size => size.name == item.name,
+                Category::Crust => menu.allows(&item.name, &size.name),
+                _ => true,
+            })
+            .collect()
+    }
+
+    // Calories in one slice of a plain pizza of a size made with an
+    // item. The crust, cheese or size replaces the house one and a
+    // topping goes on top. None if the pizza can't be made or we
+    // know nothing about one of its parts
+    pub fn pizza_slice_calories(&self, menu: &Menu, inventory: &Inventory, item: &MenuItem,
+                                size: &str) -> Option<f64> {
+        let builder = Pizza::builder().size(size);
+        let builder = match item.category {
+            Category::Size => builder,
+            Category::Crust => builder.crust(&item.name),
+            Category::Cheese => builder.cheese(&item.name),
+            _ => builder.topping(&item.name),
+        };
+        let pizza = builder.build_from(menu).ok()?;
+        Some(self.pizza(inventory, &pizza).ok()?.per_slice().calories)
+    }
+
+    // Items that keep a whole slice of a plain pizza made with them
+    // at or under a number of calories in every size they come in
+    // Items we know nothing about are left out
+    pub fn items_under<'a>(&self, menu: &'a Menu, inventory: &Inventory, calories: f64)
+                           -> Vec<&'a MenuItem> {
+        Category::ALL.iter()
+            .flat_map(|category| menu.items(*category))
+            .filter(|item| NutritionTable::sizes_for(menu, item).iter().all(|size| {
+                self.pizza_slice_calories(menu, inventory, item, &size.name)
+                    .is_some_and(|c| c <= calories)
+            }))
+            .collect()
+    }
+
+    // Calories per slice for the printed menu. A plain pizza is the
+    // house crust and cheese and the rest is what each choice adds
+    pub fn menu_text(&self, menu: &Menu, inventory: &Inventory) -> String {
+        let sizes = menu.items(Category::Size);
+        let row = |label: &str, cells: Vec<String>| {
+            let cells: Vec<String> = cells.iter().map(|c| format!("{:>8}", c)).collect();
+            format!("{:<20}{}", label, cells.join(""))
+        };
+        let calories = |result: Result<f64, NutritionError>| {
+            result.map_or(String::from("?"), |c| format!("{:.0}", c))
+        };
+
+        let mut lines = vec![
+            String::from("Calories per slice"),
+            row("", sizes.iter().map(|s| s.name.clone()).collect()),
+            row("Slices", sizes.iter()
+                .map(|s| self.slices(&s.name).map_or(String::from("?"), |n| n.to_string()))
+                .collect()),
+            row("Plain pizza", sizes.iter().map(|s| {
+                let plain = Pizza::builder().size(&s.name).build_from(menu).ok();
+                plain.and_then(|p| self.pizza(inventory, &p).ok())
+                    .map_or(String::from("?"), |n| format!("{:.0}", n.per_slice().calories))
+            }).collect()),
+        ];
+        for category in [Category::Crust, Category::Cheese, Category::Topping] {
+            lines.push(format!("{}s", category.name()));
+            for item in menu.items(category) {
+                // A dash for crusts we don't make in a size
+                let available = NutritionTable::sizes_for(menu, item);
+                lines.push(row(&format!("  {}", item.name), sizes.iter().map(|s| {
+                    if available.contains(s) {
+                        calories(self.slice_calories(inventory, item, &s.name))
+                    } else {
+                        String::from("-")
+                    }
+                }).collect()));
+            }
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,363 @@
+// An order follows the pizzas from the moment they are ordered
+// until they are served (or the customer changes their mind)
+
+use super::date;
+use super::delivery::DeliveryQuote;
+use super::dietary::{Allergen, Diet, DietaryNeeds};
+use super::json::Json;
+use super::pizza_order::Pizza;
+use super::staff::Role;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::SystemTime;
+
+// Every state an order can be in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    Placed,
+    Preparing,
+    Baking,
+    Ready,
+    Served,
+    Cancelled,
+    // Taken off the bill by a manager before it was paid
+    Voided,
+}
+
+impl OrderStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderStatus::Placed => "Placed",
+            OrderStatus::Preparing => "Preparing",
+            OrderStatus::Baking => "Baking",
+            OrderStatus::Ready => "Ready",
+            OrderStatus::Served => "Served",
+            OrderStatus::Cancelled => "Cancelled",
+            OrderStatus::Voided => "Voided",
+        }
+    }
+
+    pub const ALL: [OrderStatus; 7] = [
+        OrderStatus::Placed,
+        OrderStatus::Preparing,
+        OrderStatus::Baking,
+        OrderStatus::Ready,
+        OrderStatus::Served,
+        OrderStatus::Cancelled,
+        OrderStatus::Voided,
+    ];
+
+    // The status with this name ignoring case
+    pub fn from_name(name: &str) -> Option<OrderStatus> {
+        OrderStatus::ALL.into_iter().find(|s| s.name().eq_ignore_ascii_case(name))
+    }
+
+    // The only moves the kitchen is allowed to make
+    // Orders move forward one step at a time and can be cancelled
+    // any time before they reach the customer
+    pub fn can_become(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Placed, Preparing)
+                | (Preparing, Baking)
+                | (Baking, Ready)
+                | (Ready, Served)
+                | (Placed | Preparing | Baking | Ready, Cancelled)
+                | (Placed | Preparing | Baking | Ready | Served, Voided)
+        )
+    }
+
+    // Served, Cancelled and Voided orders never go back to the
+    // kitchen. A served order can still be voided before it is paid
+    pub fn is_finished(&self) -> bool {
+        matches!(self, OrderStatus::Served | OrderStatus::Cancelled | OrderStatus::Voided)
+    }
+}
+
+// What went wrong when changing an order
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    IllegalTransition { from: OrderStatus, to: OrderStatus },
+    // Pizzas can only be changed before the kitchen starts on them
+    TooLateToChange(OrderStatus),
+    // Pizzas on an order are numbered from 1
+    NoSuchPizza(usize),
+    // Removing the last pizza would leave nothing to make
+    LastPizza,
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::IllegalTransition { from, to } =>
+                write!(f, "An order can't go from {} to {}", from.name(), to.name()),
+            OrderError::TooLateToChange(status) =>
+                write!(f, "The order is {} so it can't be changed", status.name().to_lowercase()),
+            OrderError::NoSuchPizza(number) => write!(f, "There is no pizza {} on the order", number),
+            OrderError::LastPizza => write!(f, "An order needs a pizza, void it instead"),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+// A record of when the order entered a state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub status: OrderStatus,
+    pub at: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct Order {
+    pub id: u32,
+    pub pizzas: Vec<Pizza>,
+    // Dine in orders know which table to bring the food to and
+    // delivery orders know where the driver is going
+    pub table: Option<u32>,
+    pub delivery: Option<DeliveryQuote>,
+    pub customer: Option<String>,
+    // Coupon codes the customer gave us
+    pub coupons: Vec<String>,
+    // Loyalty points the customer wants to spend on this order
+    pub points: u32,
+    // Allergies and diets the customer told us about
+    pub needs: DietaryNeeds,
+    // The code of the store that took the order. Orders from before
+    // there was more than one store don't have one
+    pub location: Option<String>,
+    // The receipt total the customer paid in cents, after discounts
+    // and with tax, tip and delivery. None until the bill is paid
+    pub paid: Option<u32>,
+    // Who worked the order by role and name. Kept with the order so
+    // it can be reviewed after the roster is gone
+    pub staff: Vec<(Role, String)>,
+    status: OrderStatus,
+    history: Vec<Transition>,
+}
+
+// A counter shared by every thread so order numbers are never reused
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+// Hands out the next order number
+pub fn next_id() -> u32 {
+    NEXT_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+// Make sure new orders are numbered after ones loaded from disk
+pub fn reserve_ids_through(id: u32) {
+    NEXT_ID.fetch_max(id + 1, Ordering::SeqCst);
+}
+
+impl Order {
+    // New orders start out Placed
+    pub fn new(id: u32, pizzas: Vec<Pizza>) -> Order {
+        Order {
+            id,
+            pizzas,
+            table: None,
+            delivery: None,
+            customer: None,
+            coupons: Vec::new(),
+            points: 0,
+            needs: DietaryNeeds::new(),
+            location: None,
+            paid: None,
+            staff: Vec::new(),
+            status: OrderStatus::Placed,
+            history: vec![Transition {
+                status: OrderStatus::Placed,
+                at: SystemTime::now(),
+            }],
+        }
+    }
+
+    // Rebuild an order that was saved earlier. The status is the last
+    // state in its history. Returns None if the history is empty
+    pub fn restore(id: u32, pizzas: Vec<Pizza>, history: Vec<Transition>) -> Option<Order> {
+        let status = history.last()?.status;
+        Some(Order {
+            id,
+            pizzas,
+            table: None,
+            delivery: None,
+            customer: None,
+            coupons: Vec::new(),
+            points: 0,
+            needs: DietaryNeeds::new(),
+            location: None,
+            paid: None,
+            staff: Vec::new(),
+            status,
+            history,
+        })
+    }
+
+    pub fn status(&self) -> OrderStatus {
+        self.status
+    }
+
+    // Every state the order has been in, oldest first
+    pub fn history(&self) -> &[Transition] {
+        &self.history
+    }
+
+    // When the order entered a state if it ever did
+    pub fn time_of(&self, status: OrderStatus) -> Option<SystemTime> {
+        self.history.iter().find(|t| t.status == status).map(|t| t.at)
+    }
+
+    pub fn placed_at(&self) -> SystemTime {
+        self.history[0].at
+    }
+
+    // The price of every pizza in cents
+    pub fn total(&self) -> u32 {
+        self.pizzas.iter().map(|p| p.price()).sum()
+    }
+
+    // Move to a new state, refusing any move that isn't allowed
+    pub fn advance(&mut self, next: OrderStatus) -> Result<(), OrderError> {
+        if !self.status.can_become(next) {
+            return Err(OrderError::IllegalTransition {
+                from: self.status,
+                to: next,
+            });
+        }
+        self.status = next;
+        self.history.push(Transition {
+            status: next,
+            at: SystemTime::now(),
+        });
+        Ok(())
+    }
+
+    // Shortcuts for each step so callers read like the kitchen does
+    pub fn start_preparing(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Preparing)
+    }
+
+    pub fn start_baking(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Baking)
+    }
+
+    pub fn mark_ready(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Ready)
+    }
+
+    pub fn serve(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Served)
+    }
+
+    pub fn cancel(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Cancelled)
+    }
+
+    pub fn void(&mut self) -> Result<(), OrderError> {
+        self.advance(OrderStatus::Voided)
+    }
+
+    fn check_changeable(&self) -> Result<(), OrderError> {
+        if self.status != OrderStatus::Placed {
+            return Err(OrderError::TooLateToChange(self.status));
+        }
+        Ok(())
+    }
+
+    // Changes the customer makes before the kitchen starts. Pizzas
+    // are numbered from 1 like they are on the receipt
+    pub fn add_pizza(&mut self, pizza: Pizza) -> Result<(), OrderError> {
+        self.check_changeable()?;
+        self.pizzas.push(pizza);
+        Ok(())
+    }
+
+    pub fn replace_pizza(&mut self, number: usize, pizza: Pizza) -> Result<Pizza, OrderError> {
+        self.check_changeable()?;
+        let slot = number.checked_sub(1).and_then(|i| self.pizzas.get_mut(i))
+            .ok_or(OrderError::NoSuchPizza(number))?;
+        Ok(std::mem::replace(slot, pizza))
+    }
+
+    pub fn remove_pizza(&mut self, number: usize) -> Result<Pizza, OrderError> {
+        self.check_changeable()?;
+        if number == 0 || number > self.pizzas.len() {
+            return Err(OrderError::NoSuchPizza(number));
+        }
+        if self.pizzas.len() == 1 {
+            return Err(OrderError::LastPizza);
+        }
+        Ok(self.pizzas.remove(number - 1))
+    }
+
+    // Times are saved as milliseconds since 1970
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("id", Json::from(self.id)),
+            ("location", Json::from(self.location.as_deref().map(Json::string))),
+            ("customer", Json::from(self.customer.as_deref().map(Json::string))),
+            ("table", Json::from(self.table)),
+            ("delivery", Json::from(self.delivery.as_ref().map(|d| d.to_json()))),
+            ("coupons", Json::Array(self.coupons.iter().map(|c| Json::string(c)).collect())),
+            ("points", Json::from(self.points)),
+            ("allergies", Json::Array(self.needs.allergies.iter()
+                .map(|a| Json::string(a.name())).collect())),
+            ("diets", Json::Array(self.needs.diets.iter()
+                .map(|d| Json::string(d.name())).collect())),
+            ("status", Json::string(self.status.name())),
+            ("pizzas", Json::Array(self.pizzas.iter().map(|p| p.to_json()).collect())),
+            ("total", Json::from(self.total())),
+            ("paid", Json::from(self.paid)),
+            ("staff", Json::Array(self.staff.iter().map(|(role, name)| Json::object(vec![
+                ("role", Json::string(role.name())),
+                ("name", Json::string(name)),
+            ])).collect())),
+            ("history", Json::Array(self.history.iter().map(|t| Json::object(vec![
+                ("status", Json::string(t.status.name())),
+                ("at", Json::from(date::millis(t.at))),
+            ])).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<Order> {
+        let mut pizzas = Vec::new();
+        for pizza in json.get("pizzas")?.as_array()? {
+            pizzas.push(Pizza::from_json(pizza)?);
+        }
+
+        let mut history = Vec::new();
+        for t in json.get("history")?.as_array()? {
+            history.push(Transition {
+                status: OrderStatus::from_name(t.get("status")?.as_str()?)?,
+                at: date::from_millis(t.get("at")?.as_i64()?),
+            });
+        }
+
+        let mut order = Order::restore(json.get("id")?.as_u32()?, pizzas, history)?;
+        order.table = json.get("table").and_then(|t| t.as_u32());
+        order.delivery = json.get("delivery").and_then(DeliveryQuote::from_json);
+        order.points = json.get("points").and_then(|p| p.as_u32()).unwrap_or(0);
+        order.customer = json.get("customer").and_then(|c| c.as_str()).map(String::from);
+        order.location = json.get("location").and_then(|l| l.as_str()).map(String::from);
+        order.paid = json.get("paid").and_then(|p| p.as_u32());
+        if let Some(staff) = json.get("staff").and_then(|s| s.as_array()) {
+            order.staff = staff.iter().filter_map(|s| Some((
+                Role::from_name(s.get("role")?.as_str()?)?,
+                String::from(s.get("name")?.as_str()?),
+            ))).collect();
+        }
+        if let Some(coupons) = json.get("coupons").and_then(|c| c.as_array()) {
+            order.coupons = coupons.iter().filter_map(|c| c.as_str()).map(String::from).collect();
+        }
+        if let Some(allergies) = json.get("allergies").and_then(|a| a.as_array()) {
+            order.needs.allergies = allergies.iter()
+                .filter_map(|a| Allergen::from_name(a.as_str()?)).collect();
+        }
+        if let Some(diets) = json.get("diets").and_then(|d| d.as_array()) {
+            order.needs.diets = diets.iter()
+                .filter_map(|d| Diet::from_name(d.as_str()?)).collect();
+        }
+        Some(order)
+    }
+}
@@ -0,0 +1,432 @@
+// Phone staff type orders the way customers say them like
+//   large thin crust pepperoni and olives, extra cheese
+// This turns that into a pizza from the menu. Words can be in any
+// order and in any case. Filler like "a", "with" and "please" is
+// skipped and every word we don't know is reported with the menu
+// word it is closest to, so typos are caught instead of dropped
+
+// Modifiers go before the part they change : extra, double, light,
+// no, without and hold the. "cheese" means whichever cheese the
+// pizza has. Well done can go anywhere
+
+use super::menu::{Category, Menu, MenuItem};
+use super::pizza_order::{Modifier, Pizza, PizzaError};
+use std::fmt;
+
+// Words that don't change the pizza
+const FILLER: [&str; 22] = ["a", "an", "one", "1", "pizza", "pie", "with", "and", "on", "it",
+    "please", "plus", "topped", "the", "of", "some", "also", "crust", "i'd", "like", "want",
+    "get"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownWord {
+    pub word: String,
+    // The closest word we do know if any is close enough
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownWord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.suggestion {
+            Some(suggestion) => write!(f, "{} (did you mean {}?)", self.word, suggestion),
+            None => write!(f, "{}", self.word),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    Unknown(Vec<UnknownWord>),
+    // Like "large small" or "thin crust stuffed crust"
+    TwoChoices { category: Category, first: String, second: String },
+    // A modifier with nothing it can change after it
+    DanglingModifier(String),
+    // The words made sense but the pizza can't be made
+    Pizza(PizzaError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "The order is empty"),
+            ParseError::Unknown(words) => {
+                let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+                write!(f, "Didn't understand {}", words.join(", "))
+            }
+            ParseError::TwoChoices { category, first, second } =>
+                write!(f, "The order has {} and {} but a pizza only has one {}",
+                    first, second, category.name().to_lowercase()),
+            ParseError::DanglingModifier(word) =>
+                write!(f, "{} should go before a topping or cheese", word),
+            ParseError::Pizza(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<PizzaError> for ParseError {
+    fn from(e: PizzaError) -> ParseError {
+        ParseError::Pizza(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Extra,
+    Light,
+    No,
+}
+
+// What a word or run of words means
+#[derive(Debug, Clone, PartialEq)]
+enum Meaning {
+    Item(Category, String),
+    Change(Change),
+    WellDone,
+    // Whatever cheese the pizza has
+    Cheese,
+    Filler,
+}
+
+// A modifier before we know which cheese the pizza has
+enum Target {
+    Cheese,
+    Topping(String),
+}
+
+// Every phrase we understand split into words
+fn vocabulary(menu: &Menu) -> Vec<(Vec<String>, Meaning)> {
+    let mut phrases: Vec<(String, Meaning)> = Vec::new();
+    for category in Category::ALL {
+        for item in menu.items(category) {
+            for alias in aliases(item) {
+                phrases.push((alias, Meaning::Item(category, item.name.clone())));
+            }
+        }
+    }
+    for (phrase, change) in [("extra", Change::Extra), ("double", Change::Extra),
+                             ("light", Change::Light), ("easy on", Change::Light),
+                             ("no", Change::No), ("without", Change::No), ("hold", Change::No)] {
+        phrases.push((String::from(phrase), Meaning::Change(change)));
+    }
+    for phrase in ["well done", "crispy"] {
+        phrases.push((String::from(phrase), Meaning::WellDone));
+    }
+    phrases.push((String::from("cheese"), Meaning::Cheese));
+    for word in FILLER {
+        phrases.push((String::from(word), Meaning::Filler));
+    }
+
+    phrases.into_iter()
+        .map(|(phrase, meaning)| (phrase.split_whitespace().map(String::from).collect(), meaning))
+        .collect()
+}
+
+// The ways people say a menu item : olive for olives, sausages for
+// sausage and thin or thin crust for a thin crust
+fn aliases(item: &MenuItem) -> Vec<String> {
+    let name = item.name.to_lowercase();
+    let mut aliases = vec![name.clone()];
+    if item.category == Category::Topping {
+        match name.strip_suffix('s') {
+            Some(one) if one.len() > 2 => aliases.push(String::from(one)),
+            _ => aliases.push(format!("{}s", name)),
+        }
+    }
+    if item.category == Category::Crust {
+        if let Some((short, _)) = name.rsplit_once(' ') {
+            aliases.push(String::from(short));
+        }
+        if !name.ends_with(" crust") {
+            aliases.push(format!("{} crust", name));
+        }
+    }
+    aliases
+}
+
+// Lower case words with punctuation taken out
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace([',', '.', '!', ';', '&', '-', '+'], " ")
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+// Read a pizza from the menu out of what the customer said
+pub fn parse_pizza(menu: &Menu, text: &str) -> Result<Pizza, ParseError> {
+    let words = words(text);
+    if words.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    let vocabulary = vocabulary(menu);
+    let longest = vocabulary.iter().map(|(phrase, _)| phrase.len()).max().unwrap_or(1);
+
+    let mut size: Option<String> = None;
+    let mut crust: Option<String> = None;
+    let mut cheese: Option<String> = None;
+    let mut toppings: Vec<String> = Vec::new();
+    let mut changes: Vec<(Change, Target)> = Vec::new();
+    let mut well_done = false;
+    let mut unknown = Vec::new();
+    // A modifier waiting for the part it changes and the words used
+    let mut pending: Option<(Change, String)> = None;
+
+    let mut i = 0;
+    while i < words.len() {
+        // The longest phrase that matches wins so "thin crust" is
+        // read before "thin"
+        let found = (1..=longest.min(words.len() - i)).rev().find_map(|n| {
+            vocabulary.iter()
+                .find(|(phrase, _)| phrase[..] == words[i..i + n])
+                .map(|(_, meaning)| (n, meaning.clone()))
+        });
+        let Some((n, meaning)) = found else {
+            unknown.push(UnknownWord {
+                word: words[i].clone(),
+                suggestion: suggest(&words[i], &vocabulary),
+            });
+            i += 1;
+            continue;
+        };
+        let said = words[i..i + n].join(" ");
+        i += n;
+
+        match meaning {
+            Meaning::Filler => {}
+            Meaning::WellDone => well_done = true,
+            Meaning::Change(change) => {
+                if let Some((_, waiting)) = pending {
+                    return Err(ParseError::DanglingModifier(waiting));
+                }
+                pending = Some((change, said));
+            }
+            // Cheese on its own is what every pizza comes with
+            Meaning::Cheese => {
+                if let Some((change, _)) = pending.take() {
+                    changes.push((change, Target::Cheese));
+                }
+            }
+            Meaning::Item(Category::Topping, name) => match pending.take() {
+                Some((Change::No, _)) => toppings.retain(|t| *t != name),
+                change => {
+                    if !toppings.contains(&name) {
+                        toppings.push(name.clone());
+                    }
+                    if let Some((change, _)) = change {
+                        changes.push((change, Target::Topping(name)));
+                    }
+                }
+            },
+            Meaning::Item(category, name) => {
+                let choice = match category {
+                    Category::Size => &mut size,
+                    Category::Crust => &mut crust,
+                    _ => &mut cheese,
+                };
+                if let Some(first) = choice.as_ref().filter(|first| **first != name) {
+                    return Err(ParseError::TwoChoices {
+                        category,
+                        first: first.clone(),
+                        second: name,
+                    });
+                }
+                *choice = Some(name.clone());
+                if let Some((change, said)) = pending.take() {
+                    if category != Category::Cheese {
+                        return Err(ParseError::DanglingModifier(said));
+                    }
+                    changes.push((change, Target::Topping(name)));
+                }
+            }
+        }
+    }
+
+    if let Some((_, said)) = pending {
+        return Err(ParseError::DanglingModifier(said));
+    }
+    if !unknown.is_empty() {
+        return Err(ParseError::Unknown(unknown));
+    }
+
+    // A size is needed but the builder says so
+    let mut builder = Pizza::builder();
+    if let Some(size) = &size {
+        builder = builder.size(size);
+    }
+    if let Some(crust) = &crust {
+        builder = builder.crust(crust);
+    }
+    if let Some(cheese) = &cheese {
+        builder = builder.cheese(cheese);
+    }
+    for topping in &toppings {
+        builder = builder.topping(topping);
+    }
+    let cheese = cheese.unwrap_or_else(|| menu.house().cheese.clone());
+    for (change, target) in changes {
+        let part = match target {
+            Target::Cheese => cheese.clone(),
+            Target::Topping(name) => name,
+        };
+        builder = builder.modifier(match change {
+            Change::Extra => Modifier::Extra(part),
+            Change::Light => Modifier::Light(part),
+            Change::No => Modifier::No(part),
+        });
+    }
+    if well_done {
+        builder = builder.modifier(Modifier::WellDone);
+    }
+    Ok(builder.build_from(menu)?)
+}
+
+// The known word fewest typing mistakes away. Short words can be
+// 1 letter off and longer ones 2
+fn suggest(word: &str, vocabulary: &[(Vec<String>, Meaning)]) -> Option<String> {
+    let limit = if word.chars().count() <= 4 { 1 } else { 2 };
+    vocabulary.iter()
+        .filter(|(_, meaning)| *meaning != Meaning::Filler)
+        .flat_map(|(phrase, _)| phrase.iter())
+        .map(|known| (edit_distance(word, known), known))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.clone())
+}
+
+// Levenshtein distance : how many letters have to be added, removed
+// or swapped to turn one word into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Pizza, ParseError> {
+        parse_pizza(&Menu::standard(), text)
+    }
+
+    fn toppings(pizza: &Pizza) -> Vec<&str> {
+        pizza.toppings.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_words_in_any_order_and_case() {
+        let pizza = parse("Pepperoni and OLIVES on a large Thin Crust please").unwrap();
+        assert_eq!(pizza.size.name, "large");
+        assert_eq!(pizza.crust.name, "thin crust");
+        assert_eq!(pizza.cheese.name, "mozzarella");
+        assert_eq!(toppings(&pizza), vec!["pepperoni", "olives"]);
+        assert!(pizza.modifiers.is_empty());
+    }
+
+    #[test]
+    fn extra_cheese_means_the_pizzas_cheese() {
+        let pizza = parse("large pepperoni, extra cheese").unwrap();
+        assert_eq!(pizza.modifiers, vec![Modifier::Extra(String::from("mozzarella"))]);
+
+        let pizza = parse("medium cheddar pizza with extra cheese").unwrap();
+        assert_eq!(pizza.cheese.name, "cheddar");
+        assert_eq!(pizza.modifiers, vec![Modifier::Extra(String::from("cheddar"))]);
+    }
+
+    #[test]
+    fn extra_and_light_toppings_are_kept_with_the_pizza() {
+        let pizza = parse("small double pepperoni light olives well done").unwrap();
+        assert_eq!(toppings(&pizza), vec!["pepperoni", "olives"]);
+        assert_eq!(pizza.modifiers, vec![
+            Modifier::Extra(String::from("pepperoni")),
+            Modifier::Light(String::from("olives")),
+            Modifier::WellDone,
+        ]);
+    }
+
+    #[test]
+    fn no_olives_leaves_them_off() {
+        let pizza = parse("large pepperoni and olives, no olives").unwrap();
+        assert_eq!(toppings(&pizza), vec!["pepperoni"]);
+
+        let pizza = parse("large pepperoni without olive").unwrap();
+        assert_eq!(toppings(&pizza), vec!["pepperoni"]);
+    }
+
+    #[test]
+    fn typos_are_reported_with_the_closest_word() {
+        let err = parse("larg thn crust peperoni").unwrap_err();
+        assert_eq!(err, ParseError::Unknown(vec![
+            UnknownWord { word: String::from("larg"), suggestion: Some(String::from("large")) },
+            UnknownWord { word: String::from("thn"), suggestion: Some(String::from("thin")) },
+            UnknownWord {
+                word: String::from("peperoni"),
+                suggestion: Some(String::from("pepperoni")),
+            },
+        ]));
+        assert_eq!(err.to_string(), "Didn't understand larg (did you mean large?), \
+            thn (did you mean thin?), peperoni (did you mean pepperoni?)");
+
+        let err = parse("large anchovies").unwrap_err();
+        assert_eq!(err, ParseError::Unknown(vec![
+            UnknownWord { word: String::from("anchovies"), suggestion: None },
+        ]));
+    }
+
+    #[test]
+    fn two_sizes_or_crusts_are_refused() {
+        assert_eq!(parse("large pepperoni small").unwrap_err(), ParseError::TwoChoices {
+            category: Category::Size,
+            first: String::from("large"),
+            second: String::from("small"),
+        });
+        assert_eq!(parse("medium thin crust stuffed crust").unwrap_err(), ParseError::TwoChoices {
+            category: Category::Crust,
+            first: String::from("thin crust"),
+            second: String::from("stuffed crust"),
+        });
+        // Saying the same size twice is fine
+        assert_eq!(parse("large large pepperoni").unwrap().size.name, "large");
+    }
+
+    #[test]
+    fn modifiers_need_something_to_change() {
+        assert_eq!(parse("large pepperoni extra").unwrap_err(),
+            ParseError::DanglingModifier(String::from("extra")));
+        assert_eq!(parse("extra large pepperoni").unwrap_err(),
+            ParseError::DanglingModifier(String::from("extra")));
+    }
+
+    #[test]
+    fn the_pizza_still_has_to_be_on_the_menu() {
+        assert_eq!(parse("").unwrap_err(), ParseError::Empty);
+        assert_eq!(parse("pepperoni").unwrap_err(), ParseError::Pizza(PizzaError::MissingSize));
+        assert!(matches!(parse("small stuffed crust").unwrap_err(),
+            ParseError::Pizza(PizzaError::IncompatibleCrust { .. })));
+    }
+
+    #[test]
+    fn edit_distance_counts_letter_changes() {
+        assert_eq!(edit_distance("olives", "olives"), 0);
+        assert_eq!(edit_distance("olive", "olives"), 1);
+        assert_eq!(edit_distance("peperoni", "pepperoni"), 1);
+        assert_eq!(edit_distance("thn", "thin"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,400 @@
+// Promotions are rules that take money off an order
+// They are written in a TOML file so specials can change without
+// touching the code. Each [[promotion]] has a name, one discount
+// and any number of conditions :
+
+// [[promotion]]
+// name = "Lunch special"
+// percent_off = 20          # or amount_off = 2.50 or buy_one_get_one = true
+// start = "11:00"           # only between these times (UTC)
+// end = "14:00"
+// size = "small"            # only pizzas of this size count
+// code = "LUNCH"            # the customer must give this coupon code
+// expires = "2026-12-31"    # last day the code works
+// max_uses = 100            # how many times the code can be used
+
+// Every time a coupon takes money off it is saved as a line in a
+// JSON lines file so max_uses still holds after a restart
+
+use super::date::{self, Date};
+use super::json::Json;
+use super::menu::{format_price, format_rate, percent_of};
+use super::order::Order;
+use super::toml::{self, ConfigError, Document, LoadError, Table};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug)]
+pub enum RedemptionError {
+    Io(io::Error),
+    BadLine { line: usize, message: String },
+}
+
+impl fmt::Display for RedemptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RedemptionError::Io(e) => write!(f, "Coupon file error : {}", e),
+            RedemptionError::BadLine { line, message } =>
+                write!(f, "Coupon file line {} : {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for RedemptionError {}
+
+impl From<io::Error> for RedemptionError {
+    fn from(e: io::Error) -> RedemptionError {
+        RedemptionError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discount {
+    // Basis points off the pizzas the promotion covers
+    PercentOff(u32),
+    // Cents off the order
+    AmountOff(u32),
+    // For every 2 pizzas the cheaper one is free
+    BuyOneGetOne,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coupon {
+    pub code: String,
+    pub expires: Option<Date>,
+    pub max_uses: Option<u32>,
+    pub used: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Promotion {
+    pub name: String,
+    pub discount: Discount,
+    // Only pizzas of this size count towards the discount
+    pub size: Option<String>,
+    // Minutes since midnight the promotion starts and stops
+    pub hours: Option<(u32, u32)>,
+    pub coupon: Option<Coupon>,
+}
+
+// A promotion that took money off and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedPromotion {
+    pub name: String,
+    pub amount: u32,
+    pub explanation: String,
+}
+
+// The price of an order after promotions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceBreakdown {
+    pub subtotal: u32,
+    pub applied: Vec<AppliedPromotion>,
+    // Promotions that didn't apply and why, so staff can explain
+    pub skipped: Vec<String>,
+    pub total: u32,
+}
+
+impl PriceBreakdown {
+    pub fn discount(&self) -> u32 {
+        self.subtotal - self.total
+    }
+}
+
+impl Promotion {
+    // Works out the discount or says why there isn't one
+    fn check(&self, order: &Order, at: SystemTime) -> Result<AppliedPromotion, String> {
+        if let Some(coupon) = &self.coupon {
+            if !order.coupons.iter().any(|c| c.eq_ignore_ascii_case(&coupon.code)) {
+                return Err(format!("{} needs coupon code {}", self.name, coupon.code));
+            }
+            if let Some(expires) = coupon.expires {
+                if Date::of(at) > expires {
+                    return Err(format!("Coupon {} expired on {}", coupon.code, expires));
+                }
+            }
+            if let Some(max) = coupon.max_uses {
+                if coupon.used >= max {
+                    return Err(format!("Coupon {} has been used all {} times", coupon.code, max));
+                }
+            }
+        }
+
+        if let Some((start, end)) = self.hours {
+            let now = date::minute_of_day(at);
+            if now < start || now >= end {
+                return Err(format!("{} only runs from {} to {}", self.name,
+                    date::format_time_of_day(start), date::format_time_of_day(end)));
+            }
+        }
+
+        // The prices of the pizzas this promotion covers
+        let mut prices: Vec<u32> = order.pizzas.iter()
+            .filter(|p| self.size.as_ref().is_none_or(|s| p.size.name.eq_ignore_ascii_case(s)))
+            .map(|p| p.price())
+            .collect();
+        if prices.is_empty() {
+            let size = self.size.as_deref().unwrap_or("any");
+            return Err(format!("{} needs a {} pizza", self.name, size));
+        }
+
+        let (amount, explanation) = match &self.discount {
+            Discount::PercentOff(rate) => {
+                let total: u32 = prices.iter().sum();
+                (percent_of(total, *rate), format!("{} off {}", format_rate(*rate),
+                    format_price(total)))
+            }
+            Discount::AmountOff(cents) => (*cents, format!("{} off", format_price(*cents))),
+            Discount::BuyOneGetOne => {
+                if prices.len() < 2 {
+                    return Err(format!("{} needs 2 pizzas", self.name));
+                }
+                // Most expensive first so the cheaper of each pair is free
+                prices.sort_by(|a, b| b.cmp(a));
+                let free: Vec<u32> = prices.iter().skip(1).step_by(2).copied().collect();
+                (free.iter().sum(), format!("{} free pizza(s)", free.len()))
+            }
+        };
+
+        Ok(AppliedPromotion {
+            name: self.name.clone(),
+            amount,
+            explanation,
+        })
+    }
+
+    // Read one [[promotion]] table from the config file
+    fn from_table(table: &Table) -> Result<Promotion, ConfigError> {
+        table.only_keys(&["name", "percent_off", "amount_off", "buy_one_get_one",
+            "start", "end", "size", "code", "expires", "max_uses"])?;
+
+        let name = table.required("name", table.string("name"))?;
+
+        let mut discounts = Vec::new();
+        if let Some(rate) = table.percent("percent_off")? {
+            discounts.push(Discount::PercentOff(rate));
+        }
+        if let Some(cents) = table.money("amount_off")? {
+            discounts.push(Discount::AmountOff(cents));
+        }
+        if table.boolean("buy_one_get_one")? == Some(true) {
+            discounts.push(Discount::BuyOneGetOne);
+        }
+        if discounts.len() != 1 {
+            return Err(ConfigError::new(table.line, &format!(
+                "{} needs exactly one of percent_off, amount_off or buy_one_get_one", name)));
+        }
+
+        let hours = match (table.time_of_day("start")?, table.time_of_day("end")?) {
+            (None, None) => None,
+            (Some(start), Some(end)) if start < end => Some((start, end)),
+            (Some(_), Some(_)) => return Err(ConfigError::new(table.line_of("end"),
+                "end must be later than start")),
+            _ => return Err(ConfigError::new(table.line,
+                "start and end must be given together")),
+        };
+
+        let code = table.string("code")?;
+        if code.is_none() && (table.contains("expires") || table.contains("max_uses")) {
+            return Err(ConfigError::new(table.line,
+                "expires and max_uses only work with a coupon code"));
+        }
+        let coupon = match code {
+            Some(code) => Some(Coupon {
+                code,
+                expires: table.date("expires")?,
+                max_uses: match table.integer("max_uses")? {
+                    Some(n) if n > 0 => Some(n as u32),
+                    Some(_) => return Err(ConfigError::new(table.line_of("max_uses"),
+                        "max_uses must be at least 1")),
+                    None => None,
+                },
+                used: 0,
+            }),
+            None => None,
+        };
+
+        Ok(Promotion {
+            name,
+            discount: discounts.remove(0),
+            size: table.string("size")?,
+            hours,
+            coupon,
+        })
+    }
+}
+
+pub struct Promotions {
+    rules: Vec<Promotion>,
+    // Where coupon uses are saved. Without one they are only counted
+    // until the program stops
+    redemptions: Option<PathBuf>,
+}
+
+impl Promotions {
+    pub fn new() -> Promotions {
+        Promotions { rules: Vec::new(), redemptions: None }
+    }
+
+    // Count the coupon uses saved in a file and save new ones there
+    // A missing file means no coupon has been used yet
+    pub fn open_redemptions<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RedemptionError> {
+        let path = path.as_ref().to_path_buf();
+        let file = match File::open(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(RedemptionError::Io(e)),
+        };
+
+        if let Some(file) = file {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json = Json::parse(&line).map_err(|e| RedemptionError::BadLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                })?;
+                let code = json.get("code").and_then(|c| c.as_str()).ok_or(RedemptionError::BadLine {
+                    line: i + 1,
+                    message: String::from("Not a valid coupon use"),
+                })?;
+                // Codes that were taken out of the file since don't count
+                if let Some(coupon) = self.coupon_mut(code) {
+                    coupon.used += 1;
+                }
+            }
+        }
+
+        self.redemptions = Some(path);
+        Ok(())
+    }
+
+    fn coupon_mut(&mut self, code: &str) -> Option<&mut Coupon> {
+        self.rules.iter_mut()
+            .filter_map(|r| r.coupon.as_mut())
+            .find(|c| c.code.eq_ignore_ascii_case(code))
+    }
+
+    pub fn add(&mut self, promotion: Promotion) {
+        self.rules.push(promotion);
+    }
+
+    pub fn rules(&self) -> &[Promotion] {
+        &self.rules
+    }
+
+    pub fn load(path: &Path) -> Result<Promotions, LoadError> {
+        toml::load(path, Promotions::from_document)
+    }
+
+    pub fn from_document(doc: &Document) -> Result<Promotions, ConfigError> {
+        if let Some((name, line)) = doc.headings().into_iter().find(|(n, _)| *n != "promotion") {
+            return Err(ConfigError::new(line, &format!(
+                "unknown section {}, promotions go under [[promotion]]", name)));
+        }
+        let mut promotions = Promotions::new();
+        for table in doc.array("promotion") {
+            promotions.add(Promotion::from_table(table)?);
+        }
+        Ok(promotions)
+    }
+
+    // What the order costs right now. Nothing is changed so this
+    // can be used to give a customer a quote
+    pub fn quote(&self, order: &Order, at: SystemTime) -> PriceBreakdown {
+        self.price(order, at, &[]).0
+    }
+
+    // The breakdown and the rules with a coupon that took money off
+    // Rules left out are skipped with the reason given
+    fn price(&self, order: &Order, at: SystemTime, left_out: &[(usize, String)])
+             -> (PriceBreakdown, Vec<usize>) {
+        let subtotal = order.total();
+        let mut remaining = subtotal;
+        let mut applied = Vec::new();
+        let mut skipped = Vec::new();
+        let mut coupons = Vec::new();
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some((_, reason)) = left_out.iter().find(|(l, _)| *l == i) {
+                skipped.push(reason.clone());
+                continue;
+            }
+            match rule.check(order, at) {
+                // Discounts can't take the order below zero
+                Ok(mut fired) => {
+                    fired.amount = fired.amount.min(remaining);
+                    remaining -= fired.amount;
+                    if rule.coupon.is_some() && fired.amount > 0 {
+                        coupons.push(i);
+                    }
+                    applied.push(fired);
+                }
+                Err(reason) => skipped.push(reason),
+            }
+        }
+
+        // Codes the customer gave that match no promotion
+        for code in &order.coupons {
+            let known = self.rules.iter()
+                .any(|r| r.coupon.as_ref().is_some_and(|c| c.code.eq_ignore_ascii_case(code)));
+            if !known {
+                skipped.push(format!("{} is not a valid coupon code", code));
+            }
+        }
+
+        let breakdown = PriceBreakdown {
+            subtotal,
+            applied,
+            skipped,
+            total: remaining,
+        };
+        (breakdown, coupons)
+    }
+
+    // Price the order and count a use of every coupon that took
+    // money off. Uses are saved before they are counted so a use
+    // that can't be saved isn't given. The coupons are then skipped
+    // and the order priced with every other promotion. The error
+    // comes back with that breakdown so it can be shown
+    pub fn apply(&mut self, order: &Order, at: SystemTime)
+                 -> (PriceBreakdown, Option<RedemptionError>) {
+        let (breakdown, coupons) = self.price(order, at, &[]);
+        if let Err(e) = self.save_uses(order.id, &coupons, at) {
+            let left_out: Vec<(usize, String)> = coupons.iter()
+                .filter_map(|&i| Some((i, self.rules[i].coupon.as_ref()?)))
+                .map(|(i, coupon)| (i, format!("Coupon {} couldn't be saved : {}", coupon.code, e)))
+                .collect();
+            return (self.price(order, at, &left_out).0, Some(e));
+        }
+        for i in coupons {
+            if let Some(coupon) = &mut self.rules[i].coupon {
+                coupon.used += 1;
+            }
+        }
+        (breakdown, None)
+    }
+
+    fn save_uses(&self, order_id: u32, coupons: &[usize], at: SystemTime)
+                 -> Result<(), RedemptionError> {
+        let Some(path) = &self.redemptions else {
+            return Ok(());
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for &i in coupons {
+            if let Some(coupon) = &self.rules[i].coupon {
+                writeln!(file, "{}", Json::object(vec![
+                    ("code", Json::string(&coupon.code)),
+                    ("order_id", Json::from(order_id)),
+                    ("at", Json::from(date::millis(at))),
+                ]))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+// A receipt is the bill for one order
+// It lists every pizza and what went on it, then adds tax, any
+// service charge and the tip to get the total
+
+use super::json::Json;
+use super::menu::{format_price, format_rate, percent_of};
+use super::order::Order;
+use super::promotions::PriceBreakdown;
+
+// How the customer wants to tip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tip {
+    None,
+    // A share of the subtotal in basis points (1500 is 15%)
+    Percent(u32),
+    // An exact amount in cents
+    Amount(u32),
+}
+
+// Settings that are the same for every receipt we print
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptConfig {
+    // Sales tax in basis points (825 is 8.25%)
+    pub tax_rate: u32,
+    // Charge added to every bill in basis points, used for big parties
+    pub service_charge: Option<u32>,
+}
+
+impl ReceiptConfig {
+    // Our local sales tax and no service charge
+    pub fn standard() -> ReceiptConfig {
+        ReceiptConfig {
+            tax_rate: 825,
+            service_charge: None,
+        }
+    }
+}
+
+// One part of a pizza and what it added to the price
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub price: u32,
+}
+
+// One pizza on the bill
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineItem {
+    pub description: String,
+    pub components: Vec<Component>,
+    pub amount: u32,
+}
+
+// Money taken off the bill like a promotion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adjustment {
+    pub name: String,
+    pub amount: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub order_id: u32,
+    pub table: Option<u32>,
+    pub items: Vec<LineItem>,
+    pub subtotal: u32,
+    pub discounts: Vec<Adjustment>,
+    pub tax_rate: u32,
+    pub tax: u32,
+    pub service_rate: Option<u32>,
+    pub service_charge: u32,
+    pub tip: u32,
+    pub delivery_fee: u32,
+    pub total: u32,
+    // Printed under the total, like a coupon that couldn't be used
+    pub notes: Vec<String>,
+}
+
+impl Receipt {
+    pub fn for_order(order: &Order, config: &ReceiptConfig, tip: Tip) -> Receipt {
+        Receipt::discounted(order, config, tip, None)
+    }
+
+    // A receipt with the promotions that applied taken off before tax
+    pub fn discounted(order: &Order, config: &ReceiptConfig, tip: Tip,
+                      promotions: Option<&PriceBreakdown>) -> Receipt {
+        let items: Vec<LineItem> = order.pizzas.iter().map(|pizza| LineItem {
+            description: pizza.description(),
+            components: pizza.labelled_components().into_iter().map(|(name, c)| Component {
+                name,
+                price: c.price,
+            }).collect(),
+            amount: pizza.price(),
+        }).collect();
+
+        let subtotal: u32 = items.iter().map(|i| i.amount).sum();
+        let discounts = promotions.map_or(Vec::new(), |p| p.applied.iter()
+            .filter(|a| a.amount > 0)
+            .map(|a| Adjustment { name: a.name.clone(), amount: a.amount })
+            .collect());
+        let receipt = Receipt::from_items(order.id, order.table, items, subtotal,
+            discounts, config, tip);
+        receipt.with_delivery_fee(order.delivery.as_ref().map_or(0, |d| d.fee))
+    }
+
+    // Tax is charged on the subtotal after discounts. The service
+    // charge and tip are worked out from the subtotal before them
+    pub fn from_items(order_id: u32, table: Option<u32>, items: Vec<LineItem>,
+                      subtotal: u32, discounts: Vec<Adjustment>,
+                      config: &ReceiptConfig, tip: Tip) -> Receipt {
+        let discount: u32 = discounts.iter().map(|d| d.amount).sum::<u32>().min(subtotal);
+        let taxable = subtotal - discount;
+        let tax = percent_of(taxable, config.tax_rate);
+        let service_charge = config.service_charge
+            .map_or(0, |rate| percent_of(subtotal, rate));
+        let tip = match tip {
+            Tip::None => 0,
+            Tip::Percent(rate) => percent_of(subtotal, rate),
+            Tip::Amount(cents) => cents,
+        };
+
+        Receipt {
+            order_id,
+            table,
+            items,
+            subtotal,
+            discounts,
+            tax_rate: config.tax_rate,
+            tax,
+            service_rate: config.service_charge,
+            service_charge,
+            tip,
+            delivery_fee: 0,
+            total: taxable + tax + service_charge + tip,
+            notes: Vec::new(),
+        }
+    }
+
+    // The delivery fee isn't taxed or discounted so it goes on last
+    pub fn with_delivery_fee(mut self, fee: u32) -> Receipt {
+        self.total = self.total - self.delivery_fee + fee;
+        self.delivery_fee = fee;
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Receipt {
+        self.notes.push(String::from(note));
+        self
+    }
+
+    // The receipt as it is printed for the customer
+    pub fn to_text(&self) -> String {
+        let width = 40;
+        let rule = "-".repeat(width);
+        let mut lines = Vec::new();
+
+        // A label on the left and a price lined up on the right
+        let row = |label: &str, cents: u32| {
+            format!("{:<w$}{:>10}", label, format_price(cents), w = width - 10)
+        };
+
+        let mut heading = format!("Order {}", self.order_id);
+        if let Some(table) = self.table {
+            heading.push_str(&format!("  Table {}", table));
+        }
+        lines.push(heading);
+        lines.push(rule.clone());
+
+        for item in &self.items {
+            lines.push(item.description.clone());
+            for component in &item.components {
+                lines.push(row(&format!("  {}", component.name), component.price));
+            }
+            lines.push(row("  Pizza total", item.amount));
+        }
+
+        lines.push(rule.clone());
+        lines.push(row("Subtotal", self.subtotal));
+        for discount in &self.discounts {
+            lines.push(format!("{:<w$}{:>10}", discount.name,
+                format!("-{}", format_price(discount.amount)), w = width - 10));
+        }
+        lines.push(row(&format!("Tax ({})", format_rate(self.tax_rate)), self.tax));
+        if let Some(rate) = self.service_rate {
+            lines.push(row(&format!("Service ({})", format_rate(rate)), self.service_charge));
+        }
+        if self.delivery_fee > 0 {
+            lines.push(row("Delivery", self.delivery_fee));
+        }
+        if self.tip > 0 {
+            lines.push(row("Tip", self.tip));
+        }
+        lines.push(row("Total", self.total));
+        lines.extend(self.notes.iter().cloned());
+
+        lines.join("\n")
+    }
+
+    // The receipt as JSON for the point of sale system
+    // All amounts are in cents and rates in basis points
+    pub fn to_json(&self) -> Json {
+        let items = self.items.iter().map(|item| Json::object(vec![
+            ("description", Json::string(&item.description)),
+            ("components", Json::Array(item.components.iter().map(|c| Json::object(vec![
+                ("name", Json::string(&c.name)),
+                ("price", Json::from(c.price)),
+            ])).collect())),
+            ("amount", Json::from(item.amount)),
+        ])).collect();
+
+        Json::object(vec![
+            ("order_id", Json::from(self.order_id)),
+            ("table", Json::from(self.table)),
+            ("items", Json::Array(items)),
+            ("subtotal", Json::from(self.subtotal)),
+            ("discounts", Json::Array(self.discounts.iter().map(|d| Json::object(vec![
+                ("name", Json::string(&d.name)),
+                ("amount", Json::from(d.amount)),
+            ])).collect())),
+            ("tax_rate", Json::from(self.tax_rate)),
+            ("tax", Json::from(self.tax)),
+            ("service_rate", Json::from(self.service_rate)),
+            ("service_charge", Json::from(self.service_charge)),
+            ("tip", Json::from(self.tip)),
+            ("delivery_fee", Json::from(self.delivery_fee)),
+            ("total", Json::from(self.total)),
+            ("notes", Json::Array(self.notes.iter().map(|n| Json::string(n)).collect())),
+        ])
+    }
+}
@@ -0,0 +1,314 @@
+// The reservation book holds tables for parties at a set time
+// Reservations start on the half hour and keep the table for a
+// whole sitting. A table is held from one sitting before the
+// reservation so a walk in won't still be eating when they arrive
+// Parties that don't show up within the grace period lose the table
+
+// Times are minutes since midnight in UTC like promotions use
+
+use super::date::{self, Date};
+use super::seating::{Floor, Party, SeatingError};
+use std::fmt;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationStatus {
+    Booked,
+    Seated,
+    Cancelled,
+    NoShow,
+}
+
+impl ReservationStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReservationStatus::Booked => "Booked",
+            ReservationStatus::Seated => "Seated",
+            ReservationStatus::Cancelled => "Cancelled",
+            ReservationStatus::NoShow => "No show",
+        }
+    }
+
+    // Booked and seated reservations are the ones using a table
+    pub fn is_active(&self) -> bool {
+        matches!(self, ReservationStatus::Booked | ReservationStatus::Seated)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReservationError {
+    EmptyParty,
+    PartyTooLarge(u32),
+    // Reservations start on a slot like 18:00 or 18:30
+    NotOnSlot(u32),
+    // Slots have to be at least a minute apart
+    NoSlotLength,
+    OutsideHours(u32),
+    UnknownTable(u32),
+    TableTooSmall { table: u32, size: u32 },
+    // Another reservation (with) already has the table then
+    Conflict { table: u32, with: u32 },
+    FullyBooked,
+    UnknownReservation(u32),
+    NotBooked(u32, ReservationStatus),
+    Seating(SeatingError),
+}
+
+impl fmt::Display for ReservationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReservationError::EmptyParty => write!(f, "A party needs at least 1 person"),
+            ReservationError::PartyTooLarge(size) =>
+                write!(f, "We have no table for a party of {}", size),
+            ReservationError::NotOnSlot(start) =>
+                write!(f, "{} is not a reservation slot", date::format_time_of_day(*start)),
+            ReservationError::NoSlotLength =>
+                write!(f, "Reservation slots need to be at least 1 minute apart"),
+            ReservationError::OutsideHours(start) =>
+                write!(f, "We don't take reservations at {}", date::format_time_of_day(*start)),
+            ReservationError::UnknownTable(number) => write!(f, "There is no table {}", number),
+            ReservationError::TableTooSmall { table, size } =>
+                write!(f, "Table {} doesn't fit a party of {}", table, size),
+            ReservationError::Conflict { table, with } =>
+                write!(f, "Table {} is already reserved then (reservation {})", table, with),
+            ReservationError::FullyBooked => write!(f, "Every table is booked at that time"),
+            ReservationError::UnknownReservation(id) => write!(f, "There is no reservation {}", id),
+            ReservationError::NotBooked(id, status) =>
+                write!(f, "Reservation {} is {}", id, status.name().to_lowercase()),
+            ReservationError::Seating(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReservationError {}
+
+impl From<SeatingError> for ReservationError {
+    fn from(e: SeatingError) -> ReservationError {
+        ReservationError::Seating(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reservation {
+    pub id: u32,
+    pub party: Party,
+    pub table: u32,
+    pub date: Date,
+    pub start: u32,
+    pub end: u32,
+    pub status: ReservationStatus,
+}
+
+impl Reservation {
+    fn overlaps(&self, date: Date, start: u32, end: u32) -> bool {
+        self.date == date && self.start < end && start < self.end
+    }
+}
+
+pub struct ReservationBook {
+    reservations: Vec<Reservation>,
+    next_id: u32,
+    // Minutes between start times, never 0, and how long a table
+    // is kept
+    slot_minutes: u32,
+    pub sitting_minutes: u32,
+    // How late a party can be before we give the table away
+    pub grace_minutes: u32,
+    // First and last start times we take
+    pub first_slot: u32,
+    pub last_slot: u32,
+}
+
+impl ReservationBook {
+    // Half hour slots from 11:00 to 21:00, 90 minute sittings and
+    // 15 minutes grace
+    pub fn new() -> ReservationBook {
+        ReservationBook {
+            reservations: Vec::new(),
+            next_id: 1,
+            slot_minutes: 30,
+            sitting_minutes: 90,
+            grace_minutes: 15,
+            first_slot: 11 * 60,
+            last_slot: 21 * 60,
+        }
+    }
+
+    // Start times this many minutes apart instead
+    pub fn with_slot_minutes(mut self, minutes: u32) -> Result<ReservationBook, ReservationError> {
+        if minutes == 0 {
+            return Err(ReservationError::NoSlotLength);
+        }
+        self.slot_minutes = minutes;
+        Ok(self)
+    }
+
+    pub fn slot_minutes(&self) -> u32 {
+        self.slot_minutes
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Reservation> {
+        self.reservations.iter().find(|r| r.id == id)
+    }
+
+    fn get_mut(&mut self, id: u32) -> Result<&mut Reservation, ReservationError> {
+        self.reservations.iter_mut().find(|r| r.id == id)
+            .ok_or(ReservationError::UnknownReservation(id))
+    }
+
+    fn check_time(&self, start: u32) -> Result<(), ReservationError> {
+        if start < self.first_slot || start > self.last_slot {
+            return Err(ReservationError::OutsideHours(start));
+        }
+        if !(start - self.first_slot).is_multiple_of(self.slot_minutes) {
+            return Err(ReservationError::NotOnSlot(start));
+        }
+        Ok(())
+    }
+
+    // The reservation using a table over a stretch of time
+    pub fn conflict(&self, table: u32, date: Date, start: u32, end: u32) -> Option<&Reservation> {
+        self.reservations.iter()
+            .find(|r| r.table == table && r.status.is_active() && r.overlaps(date, start, end))
+    }
+
+    // Reserve the smallest table that fits and is free for the sitting
+    pub fn book(&mut self, floor: &Floor, party: Party, date: Date, start: u32)
+                -> Result<&Reservation, ReservationError> {
+        if party.size == 0 {
+            return Err(ReservationError::EmptyParty);
+        }
+        self.check_time(start)?;
+
+        let mut fits: Vec<_> = floor.tables().iter().filter(|t| t.capacity >= party.size).collect();
+        if fits.is_empty() {
+            return Err(ReservationError::PartyTooLarge(party.size));
+        }
+        fits.sort_by_key(|t| (t.capacity, t.number));
+
+        let end = start + self.sitting_minutes;
+        let table = fits.iter()
+            .find(|t| self.conflict(t.number, date, start, end).is_none())
+            .ok_or(ReservationError::FullyBooked)?;
+        let number = table.number;
+        Ok(self.add(party, number, date, start))
+    }
+
+    // Reserve one particular table, like the one by the window
+    pub fn book_table(&mut self, floor: &Floor, party: Party, table: u32, date: Date, start: u32)
+                      -> Result<&Reservation, ReservationError> {
+        if party.size == 0 {
+            return Err(ReservationError::EmptyParty);
+        }
+        self.check_time(start)?;
+        let capacity = floor.table(table).ok_or(ReservationError::UnknownTable(table))?.capacity;
+        if capacity < party.size {
+            return Err(ReservationError::TableTooSmall { table, size: party.size });
+        }
+        if let Some(other) = self.conflict(table, date, start, start + self.sitting_minutes) {
+            return Err(ReservationError::Conflict { table, with: other.id });
+        }
+        Ok(self.add(party, table, date, start))
+    }
+
+    fn add(&mut self, party: Party, table: u32, date: Date, start: u32) -> &Reservation {
+        self.reservations.push(Reservation {
+            id: self.next_id,
+            party,
+            table,
+            date,
+            start,
+            end: start + self.sitting_minutes,
+            status: ReservationStatus::Booked,
+        });
+        self.next_id += 1;
+        &self.reservations[self.reservations.len() - 1]
+    }
+
+    pub fn cancel(&mut self, id: u32) -> Result<(), ReservationError> {
+        let reservation = self.get_mut(id)?;
+        if reservation.status != ReservationStatus::Booked {
+            return Err(ReservationError::NotBooked(id, reservation.status));
+        }
+        reservation.status = ReservationStatus::Cancelled;
+        Ok(())
+    }
+
+    // The party arrived. They sit at their table even though it is
+    // held from walk ins. Returns the table number
+    pub fn check_in(&mut self, id: u32, floor: &mut Floor) -> Result<u32, ReservationError> {
+        let reservation = self.get_mut(id)?;
+        if reservation.status != ReservationStatus::Booked {
+            return Err(ReservationError::NotBooked(id, reservation.status));
+        }
+        floor.seat_at(reservation.table, reservation.party.clone())?;
+        reservation.status = ReservationStatus::Seated;
+        Ok(reservation.table)
+    }
+
+    // Every booked party more than the grace period late is marked
+    // as a no show so their table goes back to walk ins
+    pub fn mark_no_shows(&mut self, at: SystemTime) -> Vec<Reservation> {
+        let today = Date::of(at);
+        let now = date::minute_of_day(at);
+        let grace = self.grace_minutes;
+        let mut missed = Vec::new();
+        for r in self.reservations.iter_mut().filter(|r| r.status == ReservationStatus::Booked) {
+            if r.date < today || (r.date == today && r.start + grace < now) {
+                r.status = ReservationStatus::NoShow;
+                missed.push(r.clone());
+            }
+        }
+        missed
+    }
+
+    // Tables that shouldn't go to a walk in right now because a
+    // booked party is due before a sitting would be over
+    pub fn held_tables(&self, at: SystemTime) -> Vec<u32> {
+        let today = Date::of(at);
+        let now = date::minute_of_day(at);
+        let mut held: Vec<u32> = self.reservations.iter()
+            .filter(|r| r.status == ReservationStatus::Booked && r.date == today)
+            .filter(|r| now + self.sitting_minutes > r.start && now <= r.start + self.grace_minutes)
+            .map(|r| r.table)
+            .collect();
+        held.sort();
+        held.dedup();
+        held
+    }
+
+    // Start times on a day that still have a table for the party
+    pub fn open_slots(&self, floor: &Floor, date: Date, size: u32) -> Vec<u32> {
+        (self.first_slot..=self.last_slot).step_by(self.slot_minutes as usize)
+            .filter(|start| floor.tables().iter().any(|t| t.capacity >= size
+                && self.conflict(t.number, date, *start, start + self.sitting_minutes).is_none()))
+            .collect()
+    }
+
+    // Every reservation on a day in time order
+    pub fn day(&self, date: Date) -> Vec<&Reservation> {
+        let mut day: Vec<&Reservation> = self.reservations.iter()
+            .filter(|r| r.date == date)
+            .collect();
+        day.sort_by_key(|r| (r.start, r.table));
+        day
+    }
+
+    // The book as the host sees it for one day
+    pub fn day_sheet(&self, date: Date) -> String {
+        let mut lines = vec![
+            format!("Reservations for {}", date),
+            format!("{:<7}{:<7}{:<6}{:<20}{:>4}  {}", "Time", "Until", "Table", "Name", "Size",
+                "Status"),
+        ];
+        for r in self.day(date) {
+            lines.push(format!("{:<7}{:<7}{:<6}{:<20}{:>4}  {}",
+                date::format_time_of_day(r.start), date::format_time_of_day(r.end),
+                r.table, r.party.name, r.party.size, r.status.name()));
+        }
+        if lines.len() == 2 {
+            lines.push(String::from("Nothing booked"));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,391 @@
+// Customers rate a served order from 1 to 5 stars and can leave a
+// comment. Reviews are kept in a JSON lines file next to the order
+// history and each one remembers what was on the order and who
+// worked it, so ratings can be averaged for every menu item and
+// every member of staff even after the menu or roster changes
+
+// Trends compare the last few days with everything before them and
+// ratings can also be split into weeks starting on Monday
+
+use super::date::{self, Date};
+use super::json::Json;
+use super::menu::Category;
+use super::order::{Order, OrderStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const MIN_STARS: u32 = 1;
+pub const MAX_STARS: u32 = 5;
+// Comments longer than this are cut off
+pub const MAX_COMMENT: usize = 500;
+// How far the recent average has to move before it is a trend
+const TREND_STARS: f64 = 0.25;
+
+#[derive(Debug)]
+pub enum ReviewError {
+    Io(io::Error),
+    BadLine { line: usize, message: String },
+    BadRating(u32),
+    // Only orders the customer got can be reviewed
+    NotServed(u32),
+    AlreadyReviewed(u32),
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReviewError::Io(e) => write!(f, "Reviews file error : {}", e),
+            ReviewError::BadLine { line, message } =>
+                write!(f, "Reviews line {} : {}", line, message),
+            ReviewError::BadRating(stars) =>
+                write!(f, "A rating is {} to {} stars, not {}", MIN_STARS, MAX_STARS, stars),
+            ReviewError::NotServed(id) => write!(f, "Order {} hasn't been served", id),
+            ReviewError::AlreadyReviewed(id) => write!(f, "Order {} was already reviewed", id),
+        }
+    }
+}
+
+impl std::error::Error for ReviewError {}
+
+impl From<io::Error> for ReviewError {
+    fn from(e: io::Error) -> ReviewError {
+        ReviewError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Review {
+    pub order_id: u32,
+    pub at: SystemTime,
+    pub stars: u32,
+    pub comment: String,
+    pub customer: Option<String>,
+    pub location: Option<String>,
+    // Crusts, cheeses and toppings that were on the order
+    pub items: Vec<String>,
+    // Who worked the order like "Maria (host)"
+    pub staff: Vec<String>,
+}
+
+impl Review {
+    pub fn to_json(&self) -> Json {
+        let list = |names: &[String]| Json::Array(names.iter().map(|n| Json::string(n)).collect());
+        Json::object(vec![
+            ("order_id", Json::from(self.order_id)),
+            ("at", Json::from(date::millis(self.at))),
+            ("stars", Json::from(self.stars)),
+            ("comment", Json::string(&self.comment)),
+            ("customer", self.customer.as_deref().map_or(Json::Null, Json::string)),
+            ("location", self.location.as_deref().map_or(Json::Null, Json::string)),
+            ("items", list(&self.items)),
+            ("staff", list(&self.staff)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<Review> {
+        let list = |key: &str| -> Option<Vec<String>> {
+            json.get(key)?.as_array()?.iter().map(|n| n.as_str().map(String::from)).collect()
+        };
+        let text = |key: &str| json.get(key).and_then(|t| t.as_str()).map(String::from);
+        let stars = json.get("stars")?.as_u32()?;
+        if !(MIN_STARS..=MAX_STARS).contains(&stars) {
+            return None;
+        }
+        Some(Review {
+            order_id: json.get("order_id")?.as_u32()?,
+            at: date::from_millis(json.get("at")?.as_i64()?),
+            stars,
+            comment: text("comment").unwrap_or_default(),
+            customer: text("customer"),
+            location: text("location"),
+            items: list("items")?,
+            staff: list("staff")?,
+        })
+    }
+}
+
+impl fmt::Display for Review {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stars = "*".repeat(self.stars as usize);
+        let line = format!("Order {:<5}{:<7}{:<12}{}", self.order_id, stars,
+            self.customer.as_deref().unwrap_or(""), self.comment);
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+// A running total of stars
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rating {
+    pub count: u32,
+    pub stars: u32,
+}
+
+impl Rating {
+    fn add(&mut self, stars: u32) {
+        self.count += 1;
+        self.stars += stars;
+    }
+
+    // None until somebody rates it
+    pub fn average(self) -> Option<f64> {
+        (self.count > 0).then(|| self.stars as f64 / self.count as f64)
+    }
+
+    // Like 4.3 or - with no reviews
+    pub fn format(self) -> String {
+        self.average().map_or(String::from("-"), |a| format!("{:.1}", a))
+    }
+
+    pub fn to_json(self) -> Json {
+        Json::object(vec![
+            ("count", Json::from(self.count)),
+            ("stars", Json::from(self.stars)),
+            // Json only has whole numbers so the average is text
+            ("average", self.average().map_or(Json::Null, |_| Json::string(&self.format()))),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trend {
+    Up,
+    Down,
+    Steady,
+    // Nothing recent or nothing before it to compare with
+    Unknown,
+}
+
+impl Trend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Trend::Up => "up",
+            Trend::Down => "down",
+            Trend::Steady => "steady",
+            Trend::Unknown => "-",
+        }
+    }
+}
+
+// How one item, one person or the whole restaurant is rated
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingSummary {
+    pub name: String,
+    pub all: Rating,
+    // Reviews in the last few days and every review before those
+    pub recent: Rating,
+    pub earlier: Rating,
+    // Monday of each week with a review, oldest first
+    pub weeks: Vec<(Date, Rating)>,
+}
+
+impl RatingSummary {
+    pub fn trend(&self) -> Trend {
+        match (self.recent.average(), self.earlier.average()) {
+            (Some(recent), Some(earlier)) if recent - earlier >= TREND_STARS => Trend::Up,
+            (Some(recent), Some(earlier)) if earlier - recent >= TREND_STARS => Trend::Down,
+            (Some(_), Some(_)) => Trend::Steady,
+            _ => Trend::Unknown,
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("name", Json::string(&self.name)),
+            ("rating", self.all.to_json()),
+            ("recent", self.recent.to_json()),
+            ("trend", Json::string(self.trend().name())),
+            ("weeks", Json::Array(self.weeks.iter().map(|(monday, rating)| Json::object(vec![
+                ("week", Json::string(&monday.to_string())),
+                ("rating", rating.to_json()),
+            ])).collect())),
+        ])
+    }
+}
+
+pub struct ReviewBook {
+    // Without a path reviews are only kept in memory
+    path: Option<PathBuf>,
+    reviews: Vec<Review>,
+}
+
+impl ReviewBook {
+    pub fn new() -> ReviewBook {
+        ReviewBook { path: None, reviews: Vec::new() }
+    }
+
+    // Load every review. A missing file means nobody has left one
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ReviewBook, ReviewError> {
+        let path = path.as_ref().to_path_buf();
+        let mut reviews = Vec::new();
+
+        let file = match File::open(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(ReviewError::Io(e)),
+        };
+
+        if let Some(file) = file {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json = Json::parse(&line).map_err(|e| ReviewError::BadLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                })?;
+                reviews.push(Review::from_json(&json).ok_or(ReviewError::BadLine {
+                    line: i + 1,
+                    message: String::from("Not a valid review"),
+                })?);
+            }
+        }
+
+        Ok(ReviewBook { path: Some(path), reviews })
+    }
+
+    // Rate a served order. The staff rated are the ones kept with
+    // the order when it was served
+    pub fn add(&mut self, order: &Order, stars: u32, comment: &str) -> Result<&Review, ReviewError> {
+        if !(MIN_STARS..=MAX_STARS).contains(&stars) {
+            return Err(ReviewError::BadRating(stars));
+        }
+        if order.status() != OrderStatus::Served {
+            return Err(ReviewError::NotServed(order.id));
+        }
+        if self.get(order.id).is_some() {
+            return Err(ReviewError::AlreadyReviewed(order.id));
+        }
+
+        let mut items: Vec<String> = Vec::new();
+        for item in order.pizzas.iter().flat_map(|p| p.components()) {
+            if item.category != Category::Size && !items.contains(&item.name) {
+                items.push(item.name.clone());
+            }
+        }
+        let review = Review {
+            order_id: order.id,
+            at: SystemTime::now(),
+            stars,
+            comment: comment.trim().chars().take(MAX_COMMENT).collect(),
+            customer: order.customer.clone(),
+            location: order.location.clone(),
+            items,
+            staff: order.staff.iter().map(|(role, name)| format!("{} ({})", name, role.name())).collect(),
+        };
+
+        // Written to the file first so memory never has a review
+        // the file doesn't
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", review.to_json())?;
+        }
+        self.reviews.push(review);
+        Ok(&self.reviews[self.reviews.len() - 1])
+    }
+
+    // Oldest first
+    pub fn reviews(&self) -> &[Review] {
+        &self.reviews
+    }
+
+    pub fn get(&self, order_id: u32) -> Option<&Review> {
+        self.reviews.iter().find(|r| r.order_id == order_id)
+    }
+
+    // Every review together. Recent is the last days up to today
+    pub fn overall(&self, today: Date, days: u32) -> RatingSummary {
+        summarize("All", self.reviews.iter(), today, days)
+    }
+
+    // Best rated first
+    pub fn item_ratings(&self, today: Date, days: u32) -> Vec<RatingSummary> {
+        self.ratings_by(|r| &r.items, today, days)
+    }
+
+    pub fn staff_ratings(&self, today: Date, days: u32) -> Vec<RatingSummary> {
+        self.ratings_by(|r| &r.staff, today, days)
+    }
+
+    fn ratings_by<F>(&self, names: F, today: Date, days: u32) -> Vec<RatingSummary>
+    where
+        F: Fn(&Review) -> &Vec<String>,
+    {
+        let mut by_name: HashMap<&str, Vec<&Review>> = HashMap::new();
+        for review in &self.reviews {
+            for name in names(review) {
+                by_name.entry(name).or_default().push(review);
+            }
+        }
+        let mut summaries: Vec<RatingSummary> = by_name.into_iter()
+            .map(|(name, reviews)| summarize(name, reviews.into_iter(), today, days))
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.all.average().partial_cmp(&a.all.average()).unwrap()
+                .then(b.all.count.cmp(&a.all.count))
+                .then(a.name.cmp(&b.name))
+        });
+        summaries
+    }
+
+    // What the manager reads each week
+    pub fn report(&self, today: Date, days: u32) -> String {
+        let heading = format!("{:<24}{:>8}{:>9}{:>9}{:>9}", "", "Reviews", "Average",
+            format!("{} days", days), "Trend");
+        let row = |s: &RatingSummary| {
+            format!("{:<24}{:>8}{:>9}{:>9}{:>9}", s.name, s.all.count, s.all.format(),
+                s.recent.format(), s.trend().name())
+        };
+
+        let overall = self.overall(today, days);
+        let mut lines = vec![format!("Reviews up to {}", today), heading.clone(), row(&overall)];
+        lines.push(String::from("By item"));
+        lines.extend(self.item_ratings(today, days).iter().map(row));
+        lines.push(String::from("By staff"));
+        lines.extend(self.staff_ratings(today, days).iter().map(row));
+        lines.push(String::from("By week"));
+        for (monday, rating) in &overall.weeks {
+            lines.push(format!("{:<24}{:>8}{:>9}", format!("  {}", monday), rating.count,
+                rating.format()));
+        }
+        lines.push(String::from("Latest"));
+        lines.extend(self.reviews.iter().rev().take(5).map(|r| format!("  {}", r)));
+        lines.join("\n")
+    }
+}
+
+fn summarize<'a, I>(name: &str, reviews: I, today: Date, days: u32) -> RatingSummary
+where
+    I: Iterator<Item = &'a Review>,
+{
+    // The first day that counts as recent
+    let since = Date::from_days(today.days() - days.max(1) as i64 + 1);
+    let mut summary = RatingSummary {
+        name: String::from(name),
+        all: Rating::default(),
+        recent: Rating::default(),
+        earlier: Rating::default(),
+        weeks: Vec::new(),
+    };
+    let mut weeks: HashMap<Date, Rating> = HashMap::new();
+    for review in reviews {
+        let day = Date::of(review.at);
+        if day > today {
+            continue;
+        }
+        summary.all.add(review.stars);
+        if day >= since {
+            summary.recent.add(review.stars);
+        } else {
+            summary.earlier.add(review.stars);
+        }
+        weeks.entry(day.monday()).or_default().add(review.stars);
+    }
+    summary.weeks = weeks.into_iter().collect();
+    summary.weeks.sort_by_key(|(monday, _)| *monday);
+    summary
+}
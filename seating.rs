@@ -0,0 +1,223 @@
+// The floor is every table in the dining room
+// Parties are put at the smallest free table they fit at so big
+// tables stay open for big groups. When nothing fits they wait
+// in line until a table is released
+
+use super::dietary::DietaryNeeds;
+use std::collections::VecDeque;
+use std::fmt;
+
+// A group of customers eating together
+#[derive(Debug, Clone, PartialEq)]
+pub struct Party {
+    pub name: String,
+    pub size: u32,
+    // Allergies and diets the server should know about
+    pub needs: DietaryNeeds,
+}
+
+impl Party {
+    pub fn new(name: &str, size: u32) -> Party {
+        Party {
+            name: String::from(name),
+            size,
+            needs: DietaryNeeds::new(),
+        }
+    }
+
+    pub fn with_needs(mut self, needs: DietaryNeeds) -> Party {
+        self.needs = needs;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub number: u32,
+    pub capacity: u32,
+    pub party: Option<Party>,
+}
+
+impl Table {
+    pub fn is_free(&self) -> bool {
+        self.party.is_none()
+    }
+}
+
+// Where a party ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Seating {
+    // The table number they are sitting at
+    Seated(u32),
+    // Their place in line starting at 1
+    Waitlisted(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeatingError {
+    EmptyParty,
+    // No table in the restaurant is big enough
+    PartyTooLarge(u32),
+    UnknownTable(u32),
+    TableAlreadyExists(u32),
+    TableNotOccupied(u32),
+    TableOccupied(u32),
+}
+
+impl fmt::Display for SeatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatingError::EmptyParty => write!(f, "A party needs at least 1 person"),
+            SeatingError::PartyTooLarge(size) =>
+                write!(f, "We have no table for a party of {}", size),
+            SeatingError::UnknownTable(number) => write!(f, "There is no table {}", number),
+            SeatingError::TableAlreadyExists(number) =>
+                write!(f, "Table {} already exists", number),
+            SeatingError::TableNotOccupied(number) =>
+                write!(f, "Nobody is sitting at table {}", number),
+            SeatingError::TableOccupied(number) =>
+                write!(f, "Someone is already sitting at table {}", number),
+        }
+    }
+}
+
+impl std::error::Error for SeatingError {}
+
+pub struct Floor {
+    tables: Vec<Table>,
+    waitlist: VecDeque<Party>,
+    // Tables kept for reservations that walk ins can't have
+    held: Vec<u32>,
+}
+
+impl Floor {
+    pub fn new() -> Floor {
+        Floor {
+            tables: Vec::new(),
+            waitlist: VecDeque::new(),
+            held: Vec::new(),
+        }
+    }
+
+    // Our dining room : 4 two tops, 3 four tops and 1 big table
+    pub fn standard() -> Floor {
+        let mut floor = Floor::new();
+        let layout = [(1, 2), (2, 2), (3, 2), (4, 2), (5, 4), (6, 4), (7, 4), (8, 8)];
+        for (number, capacity) in layout {
+            // Numbers above are unique so this can't fail
+            floor.add_table(number, capacity).unwrap();
+        }
+        floor
+    }
+
+    pub fn add_table(&mut self, number: u32, capacity: u32) -> Result<(), SeatingError> {
+        if self.table(number).is_some() {
+            return Err(SeatingError::TableAlreadyExists(number));
+        }
+        self.tables.push(Table { number, capacity, party: None });
+        Ok(())
+    }
+
+    pub fn table(&self, number: u32) -> Option<&Table> {
+        self.tables.iter().find(|t| t.number == number)
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn free_tables(&self) -> Vec<&Table> {
+        self.tables.iter().filter(|t| t.is_free()).collect()
+    }
+
+    // Parties waiting for a table, first in line first
+    pub fn waitlist(&self) -> &VecDeque<Party> {
+        &self.waitlist
+    }
+
+    // Replace the tables held for reservations
+    pub fn hold_tables(&mut self, tables: &[u32]) {
+        self.held = tables.to_vec();
+    }
+
+    pub fn is_held(&self, number: u32) -> bool {
+        self.held.contains(&number)
+    }
+
+    // The smallest free table that fits the party and isn't held
+    fn best_fit(&self, size: u32) -> Option<usize> {
+        self.tables.iter()
+            .enumerate()
+            .filter(|(_, t)| t.is_free() && t.capacity >= size && !self.is_held(t.number))
+            .min_by_key(|(_, t)| (t.capacity, t.number))
+            .map(|(i, _)| i)
+    }
+
+    // Seat a party right away or add them to the waitlist
+    pub fn seat(&mut self, party: Party) -> Result<Seating, SeatingError> {
+        if party.size == 0 {
+            return Err(SeatingError::EmptyParty);
+        }
+        if !self.tables.iter().any(|t| t.capacity >= party.size) {
+            return Err(SeatingError::PartyTooLarge(party.size));
+        }
+
+        match self.best_fit(party.size) {
+            Some(i) => {
+                self.tables[i].party = Some(party);
+                Ok(Seating::Seated(self.tables[i].number))
+            }
+            None => {
+                self.waitlist.push_back(party);
+                Ok(Seating::Waitlisted(self.waitlist.len()))
+            }
+        }
+    }
+
+    // Seat a party at a particular table even if it is held, like a
+    // party arriving for their reservation
+    pub fn seat_at(&mut self, number: u32, party: Party) -> Result<(), SeatingError> {
+        if party.size == 0 {
+            return Err(SeatingError::EmptyParty);
+        }
+        let table = self.tables.iter_mut()
+            .find(|t| t.number == number)
+            .ok_or(SeatingError::UnknownTable(number))?;
+        if !table.is_free() {
+            return Err(SeatingError::TableOccupied(number));
+        }
+        table.party = Some(party);
+        Ok(())
+    }
+
+    // The party at a table leaves. Anyone waiting who now fits is
+    // seated in the order they arrived. Returns the party that left
+    // and the parties seated from the waitlist with their tables
+    pub fn release(&mut self, number: u32) -> Result<(Party, Vec<(Party, u32)>), SeatingError> {
+        let table = self.tables.iter_mut()
+            .find(|t| t.number == number)
+            .ok_or(SeatingError::UnknownTable(number))?;
+        let left = table.party.take().ok_or(SeatingError::TableNotOccupied(number))?;
+
+        let mut seated = Vec::new();
+        let mut still_waiting = VecDeque::new();
+        while let Some(party) = self.waitlist.pop_front() {
+            match self.best_fit(party.size) {
+                Some(i) => {
+                    self.tables[i].party = Some(party.clone());
+                    seated.push((party, self.tables[i].number));
+                }
+                None => still_waiting.push_back(party),
+            }
+        }
+        self.waitlist = still_waiting;
+
+        Ok((left, seated))
+    }
+
+    // A party on the waitlist gives up and leaves
+    pub fn leave_waitlist(&mut self, name: &str) -> Option<Party> {
+        let i = self.waitlist.iter().position(|p| p.name == name)?;
+        self.waitlist.remove(i)
+    }
+}
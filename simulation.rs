@@ -0,0 +1,440 @@
+// A simulation answers questions like "how many cooks do we need for
+// a Friday rush" without waiting for a real Friday
+// Parties arrive at random, get a table from the real Floor, order
+// pizzas from the menu and wait for a cook. Nothing actually waits :
+// every step is an event at a simulated time and the next event is
+// always the earliest one (a discrete event simulation)
+
+// The same seed always gives the same customers so two setups can
+// be compared fairly. Times are whole seconds since opening
+
+use super::analytics::{self, format_duration};
+use super::menu::{format_price, format_rate, Category, Menu};
+use super::order::Order;
+use super::pizza_order::Pizza;
+use super::seating::{Floor, Party, Seating};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::time::Duration;
+
+// How likely each party size is, out of 100
+const PARTY_SIZES: [(u32, u32); 8] =
+    [(1, 10), (2, 40), (3, 15), (4, 20), (5, 6), (6, 5), (7, 2), (8, 2)];
+
+// Everything that can be changed between runs. Times are minutes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub seed: u64,
+    pub cooks: u32,
+    // Table numbers and seats
+    pub tables: Vec<(u32, u32)>,
+    // How long the doors are open to new parties
+    pub hours: u32,
+    // Parties arriving in an average hour
+    pub arrivals_per_hour: f64,
+    // From sitting down to giving the order
+    pub order_minutes: u32,
+    // Cook time for each pizza plus oven time for the order
+    pub prep_minutes: u32,
+    pub bake_minutes: u32,
+    // Parties eat for somewhere in this range
+    pub eat_minutes: (u32, u32),
+    // How long a party will stand on the waitlist
+    pub patience_minutes: u32,
+}
+
+impl Scenario {
+    // A busy Friday night on the standard floor
+    pub fn friday_rush() -> Scenario {
+        Scenario {
+            seed: 42,
+            cooks: 3,
+            tables: Floor::standard().tables().iter().map(|t| (t.number, t.capacity)).collect(),
+            hours: 4,
+            arrivals_per_hour: 14.0,
+            order_minutes: 5,
+            prep_minutes: 4,
+            bake_minutes: 10,
+            eat_minutes: (30, 50),
+            patience_minutes: 30,
+        }
+    }
+
+    // Chain these to change one thing
+    pub fn seed(mut self, seed: u64) -> Scenario {
+        self.seed = seed;
+        self
+    }
+
+    pub fn cooks(mut self, cooks: u32) -> Scenario {
+        self.cooks = cooks.max(1);
+        self
+    }
+
+    pub fn tables(mut self, tables: &[(u32, u32)]) -> Scenario {
+        self.tables = tables.to_vec();
+        self
+    }
+
+    pub fn arrivals_per_hour(mut self, arrivals: f64) -> Scenario {
+        self.arrivals_per_hour = arrivals;
+        self
+    }
+
+    // Run the night and report how it went
+    pub fn run(&self, menu: &Menu) -> SimReport {
+        Run::new(self, menu).finish()
+    }
+}
+
+// Things that happen. Derives Ord so events at the same second can
+// sit in the heap, ties go to whichever was scheduled first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Arrive(usize),
+    // The party at a table is ready to order
+    Order(u32),
+    // An order comes out of the oven
+    Ready(u32),
+    // The party at a table pays and leaves
+    Leave(u32),
+    // A party on the waitlist gives up
+    GiveUp(usize),
+}
+
+// What happened to one party
+#[derive(Debug, Clone, Default)]
+struct Visit {
+    size: u32,
+    arrived: u32,
+    seated: Option<u32>,
+    ordered: Option<u32>,
+    served: Option<u32>,
+    left: Option<u32>,
+    gave_up: bool,
+    turned_away: bool,
+}
+
+struct Run<'a> {
+    scenario: &'a Scenario,
+    menu: &'a Menu,
+    rng: StdRng,
+    now: u32,
+    events: BinaryHeap<Reverse<(u32, u64, Event)>>,
+    scheduled: u64,
+    visits: Vec<Visit>,
+    floor: Floor,
+    // Which party is at each table
+    seated: HashMap<u32, usize>,
+    // Orders waiting for a cook, then orders being cooked, with the
+    // table they go to
+    queue: VecDeque<(Order, u32)>,
+    cooking: HashMap<u32, (Order, u32)>,
+    next_order: u32,
+    busy_cooks: u32,
+    cook_seconds: u64,
+    longest_queue: usize,
+    revenue: u32,
+}
+
+impl<'a> Run<'a> {
+    fn new(scenario: &'a Scenario, menu: &'a Menu) -> Run<'a> {
+        let mut floor = Floor::new();
+        for (number, seats) in &scenario.tables {
+            // A repeated number is skipped rather than stopping the run
+            let _ = floor.add_table(*number, *seats);
+        }
+        let mut run = Run {
+            scenario,
+            menu,
+            rng: StdRng::seed_from_u64(scenario.seed),
+            now: 0,
+            events: BinaryHeap::new(),
+            scheduled: 0,
+            visits: Vec::new(),
+            floor,
+            seated: HashMap::new(),
+            queue: VecDeque::new(),
+            cooking: HashMap::new(),
+            next_order: 1,
+            busy_cooks: 0,
+            cook_seconds: 0,
+            longest_queue: 0,
+            revenue: 0,
+        };
+        run.plan_arrivals();
+        run
+    }
+
+    fn schedule(&mut self, at: u32, event: Event) {
+        self.events.push(Reverse((at, self.scheduled, event)));
+        self.scheduled += 1;
+    }
+
+    // Time between arrivals is exponential, which is how customers
+    // that don't know about each other turn up
+    fn plan_arrivals(&mut self) {
+        let closing = self.scenario.hours * 3_600;
+        let mean_gap = 3_600.0 / self.scenario.arrivals_per_hour.max(0.01);
+        let mut at = 0.0;
+        loop {
+            let u: f64 = self.rng.gen();
+            at += -(1.0 - u).ln() * mean_gap;
+            if at >= closing as f64 {
+                break;
+            }
+            let size = self.party_size();
+            self.visits.push(Visit { size, arrived: at as u32, ..Visit::default() });
+            self.schedule(at as u32, Event::Arrive(self.visits.len() - 1));
+        }
+    }
+
+    fn party_size(&mut self) -> u32 {
+        let mut roll = self.rng.gen_range(0..100);
+        for (size, chance) in PARTY_SIZES {
+            if roll < chance {
+                return size;
+            }
+            roll -= chance;
+        }
+        2
+    }
+
+    fn minutes(&mut self, range: (u32, u32)) -> u32 {
+        self.rng.gen_range(range.0..=range.1.max(range.0)) * 60
+    }
+
+    fn finish(mut self) -> SimReport {
+        while let Some(Reverse((at, _, event))) = self.events.pop() {
+            self.now = at;
+            match event {
+                Event::Arrive(party) => self.arrive(party),
+                Event::Order(table) => self.order(table),
+                Event::Ready(id) => self.ready(id),
+                Event::Leave(table) => self.leave(table),
+                Event::GiveUp(party) => self.give_up(party),
+            }
+        }
+        self.report()
+    }
+
+    fn arrive(&mut self, party: usize) {
+        let guests = Party::new(&format!("Party {}", party + 1), self.visits[party].size);
+        match self.floor.seat(guests) {
+            Ok(Seating::Seated(table)) => self.sit(party, table),
+            Ok(Seating::Waitlisted(_)) => {
+                let at = self.now + self.scenario.patience_minutes * 60;
+                self.schedule(at, Event::GiveUp(party));
+            }
+            // Too big for every table
+            Err(_) => self.visits[party].turned_away = true,
+        }
+    }
+
+    fn sit(&mut self, party: usize, table: u32) {
+        self.visits[party].seated = Some(self.now);
+        self.seated.insert(table, party);
+        self.schedule(self.now + self.scenario.order_minutes * 60, Event::Order(table));
+    }
+
+    fn give_up(&mut self, party: usize) {
+        // Parties that already got a table aren't on the waitlist
+        if self.floor.leave_waitlist(&format!("Party {}", party + 1)).is_some() {
+            self.visits[party].gave_up = true;
+        }
+    }
+
+    // A pizza for every two people with a random size and toppings
+    fn order(&mut self, table: u32) {
+        let party = self.seated[&table];
+        let sizes = self.menu.items(Category::Size);
+        let toppings = self.menu.items(Category::Topping);
+        let mut pizzas = Vec::new();
+        for _ in 0..self.visits[party].size.div_ceil(2) {
+            let mut builder = Pizza::builder()
+                .size(&sizes[self.rng.gen_range(0..sizes.len())].name);
+            for _ in 0..self.rng.gen_range(0..=2) {
+                if !toppings.is_empty() {
+                    builder = builder.topping(&toppings[self.rng.gen_range(0..toppings.len())].name);
+                }
+            }
+            // A repeated topping isn't allowed so they settle for lunch
+            match builder.build_from(self.menu) {
+                Ok(pizza) => pizzas.push(pizza),
+                Err(_) => pizzas.extend(Pizza::lunch_from(self.menu, "veggies").ok()),
+            }
+        }
+
+        let order = Order::new(self.next_order, pizzas);
+        self.next_order += 1;
+        self.visits[party].ordered = Some(self.now);
+        self.queue.push_back((order, table));
+        self.longest_queue = self.longest_queue.max(self.queue.len());
+        self.start_cooking();
+    }
+
+    // Free cooks take orders in the order they came in
+    fn start_cooking(&mut self) {
+        while self.busy_cooks < self.scenario.cooks {
+            let Some((mut order, table)) = self.queue.pop_front() else {
+                break;
+            };
+            let _ = order.start_preparing().and_then(|_| order.start_baking());
+            let seconds = (self.scenario.prep_minutes * order.pizzas.len() as u32
+                + self.scenario.bake_minutes) * 60;
+            self.busy_cooks += 1;
+            self.cook_seconds += seconds as u64;
+            self.schedule(self.now + seconds, Event::Ready(order.id));
+            self.cooking.insert(order.id, (order, table));
+        }
+    }
+
+    fn ready(&mut self, id: u32) {
+        self.busy_cooks -= 1;
+        if let Some((mut order, table)) = self.cooking.remove(&id) {
+            let _ = order.mark_ready().and_then(|_| order.serve());
+            self.revenue += order.total();
+            self.visits[self.seated[&table]].served = Some(self.now);
+            let eat = self.minutes(self.scenario.eat_minutes);
+            self.schedule(self.now + eat, Event::Leave(table));
+        }
+        self.start_cooking();
+    }
+
+    fn leave(&mut self, table: u32) {
+        if let Some(party) = self.seated.remove(&table) {
+            self.visits[party].left = Some(self.now);
+        }
+        if let Ok((_, seated)) = self.floor.release(table) {
+            for (guests, table) in seated {
+                // Names are "Party n" so the number finds the visit
+                if let Some(party) = guests.name.strip_prefix("Party ")
+                    .and_then(|n| n.parse::<usize>().ok()) {
+                    self.sit(party - 1, table);
+                }
+            }
+        }
+    }
+
+    fn report(&self) -> SimReport {
+        let seconds = |s: u32| Duration::from_secs(s as u64);
+        let mut seat_waits: Vec<Duration> = self.visits.iter()
+            .filter_map(|v| v.seated.map(|s| seconds(s - v.arrived)))
+            .collect();
+        seat_waits.sort();
+        let mut food_waits: Vec<Duration> = self.visits.iter()
+            .filter_map(|v| Some(seconds(v.served? - v.ordered?)))
+            .collect();
+        food_waits.sort();
+
+        let open = self.now.max(1) as u64;
+        let table_seconds: u64 = self.visits.iter()
+            .filter_map(|v| Some((v.left? - v.seated?) as u64))
+            .sum();
+        let tables = self.floor.tables().len().max(1) as u64;
+
+        SimReport {
+            scenario: self.scenario.clone(),
+            parties: self.visits.len() as u32,
+            served: self.visits.iter().filter(|v| v.served.is_some()).count() as u32,
+            guests: self.visits.iter().filter(|v| v.served.is_some()).map(|v| v.size).sum(),
+            gave_up: self.visits.iter().filter(|v| v.gave_up).count() as u32,
+            turned_away: self.visits.iter().filter(|v| v.turned_away).count() as u32,
+            waited: seat_waits.iter().filter(|w| !w.is_zero()).count() as u32,
+            seat_wait: Waits::of(&seat_waits),
+            food_wait: Waits::of(&food_waits),
+            longest_queue: self.longest_queue,
+            cook_use: (self.cook_seconds * 10_000 / (open * self.scenario.cooks as u64)) as u32,
+            table_use: (table_seconds * 10_000 / (open * tables)) as u32,
+            last_out: seconds(self.now),
+            revenue: self.revenue,
+        }
+    }
+}
+
+// The middle, 90th percentile and longest of some waits
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Waits {
+    pub median: Duration,
+    pub p90: Duration,
+    pub longest: Duration,
+}
+
+impl Waits {
+    fn of(sorted: &[Duration]) -> Waits {
+        match sorted.last() {
+            None => Waits::default(),
+            Some(longest) => Waits {
+                median: analytics::percentile(sorted, 50),
+                p90: analytics::percentile(sorted, 90),
+                longest: *longest,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimReport {
+    pub scenario: Scenario,
+    pub parties: u32,
+    pub served: u32,
+    // People in the parties that were served
+    pub guests: u32,
+    pub gave_up: u32,
+    // Parties bigger than every table
+    pub turned_away: u32,
+    // Parties that didn't get a table right away
+    pub waited: u32,
+    // Arriving to sitting down, and ordering to eating
+    pub seat_wait: Waits,
+    pub food_wait: Waits,
+    pub longest_queue: usize,
+    // Share of the night cooks and tables were busy in basis points
+    pub cook_use: u32,
+    pub table_use: u32,
+    // When the last party left
+    pub last_out: Duration,
+    pub revenue: u32,
+}
+
+impl SimReport {
+    // One line for comparing runs side by side
+    pub fn summary(&self) -> String {
+        format!("{:>5}{:>8}{:>8}{:>12}{:>12}{:>10}{:>10}",
+            self.scenario.cooks, self.served, self.gave_up,
+            format_duration(self.seat_wait.p90), format_duration(self.food_wait.p90),
+            format_rate(self.cook_use), format_rate(self.table_use))
+    }
+
+    pub fn summary_heading() -> String {
+        format!("{:>5}{:>8}{:>8}{:>12}{:>12}{:>10}{:>10}",
+            "Cooks", "Served", "Left", "Seat p90", "Food p90", "Cooks", "Tables")
+    }
+
+    pub fn to_text(&self) -> String {
+        let s = &self.scenario;
+        let waits = |label: &str, w: &Waits| format!("{:<20}{:>10}{:>10}{:>10}", label,
+            format_duration(w.median), format_duration(w.p90), format_duration(w.longest));
+        [
+            format!("Simulated {} hours, {} cooks, {} tables, {:.0} parties an hour (seed {})",
+                s.hours, s.cooks, s.tables.len(), s.arrivals_per_hour, s.seed),
+            format!("Parties arrived      {:>8}", self.parties),
+            format!("Parties served       {:>8}  ({} guests)", self.served, self.guests),
+            format!("Waited for a table   {:>8}", self.waited),
+            format!("Gave up waiting      {:>8}", self.gave_up),
+            format!("No table big enough  {:>8}", self.turned_away),
+            format!("Sales                {:>8}", format_price(self.revenue)),
+            format!("Last party left      {:>8}", format_duration(self.last_out)),
+            String::new(),
+            format!("{:<20}{:>10}{:>10}{:>10}", "Wait", "Median", "90th", "Longest"),
+            waits("For a table", &self.seat_wait),
+            waits("For food", &self.food_wait),
+            String::new(),
+            format!("Cooks busy           {:>8}", format_rate(self.cook_use)),
+            format!("Tables in use        {:>8}", format_rate(self.table_use)),
+            format!("Most orders waiting  {:>8}", self.longest_queue),
+        ].join("\n")
+    }
+}
@@ -0,0 +1,381 @@
+// Splitting a bill lets a group pay separately
+// Every part of the bill (subtotal, discounts, tax, service, tip and
+// delivery) is shared out on its own using the largest remainder
+// method : everyone gets their share rounded down and the cents
+// left over go to whoever lost the most to rounding. That way the
+// shares of each part add up exactly and so do the payers' totals
+
+use super::json::Json;
+use super::menu::format_price;
+use super::receipt::Receipt;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Split {
+    // Everyone pays the same
+    Evenly,
+    // The pizzas (counting from 1) each payer had. A pizza listed
+    // by more than one payer is shared between them
+    ByItem(Vec<Vec<usize>>),
+    // How much each payer will pay. Payers with None share whatever
+    // the others don't cover
+    ByAmount(Vec<Option<u32>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitError {
+    NoPayers,
+    // The split doesn't list the same number of payers
+    WrongPayerCount { payers: usize, shares: usize },
+    UnknownItem(usize),
+    ItemNotClaimed(usize),
+    AmountsDontAddUp { total: u32, given: u32 },
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitError::NoPayers => write!(f, "A bill needs at least 1 payer"),
+            SplitError::WrongPayerCount { payers, shares } =>
+                write!(f, "There are {} payers but {} shares", payers, shares),
+            SplitError::UnknownItem(item) => write!(f, "There is no pizza {} on the bill", item),
+            SplitError::ItemNotClaimed(item) => write!(f, "Nobody is paying for pizza {}", item),
+            SplitError::AmountsDontAddUp { total, given } =>
+                write!(f, "The bill is {} but the amounts add up to {}",
+                    format_price(*total), format_price(*given)),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+// A payer's part of one pizza
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemShare {
+    pub description: String,
+    pub amount: u32,
+    // How many people are sharing the pizza
+    pub shared_by: usize,
+}
+
+// What one payer owes
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayerReceipt {
+    pub payer: String,
+    pub order_id: u32,
+    pub items: Vec<ItemShare>,
+    pub subtotal: u32,
+    pub discount: u32,
+    pub tax: u32,
+    pub service_charge: u32,
+    pub tip: u32,
+    pub delivery_fee: u32,
+    pub total: u32,
+}
+
+// Share an amount out by weight so the shares add up exactly
+// Equal weights are used when every weight is 0
+pub fn allocate(amount: u32, weights: &[u32]) -> Vec<u32> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+    let weights: Vec<u64> = if weights.iter().all(|w| *w == 0) {
+        vec![1; weights.len()]
+    } else {
+        weights.iter().map(|w| *w as u64).collect()
+    };
+    let total: u64 = weights.iter().sum();
+
+    let mut shares: Vec<u32> = weights.iter()
+        .map(|w| (amount as u64 * w / total) as u32)
+        .collect();
+
+    // Hand out the leftover cents biggest remainder first. Ties go
+    // to whoever is listed first
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(amount as u64 * weights[*i] % total));
+    let left = amount - shares.iter().sum::<u32>();
+    for i in order.into_iter().take(left as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+// Split a receipt between payers. Returns one receipt per payer
+// in the same order as the names
+pub fn split(receipt: &Receipt, payers: &[&str], how: &Split)
+             -> Result<Vec<PayerReceipt>, SplitError> {
+    if payers.is_empty() {
+        return Err(SplitError::NoPayers);
+    }
+    let n = payers.len();
+    let mut bills: Vec<PayerReceipt> = payers.iter().map(|payer| PayerReceipt {
+        payer: String::from(*payer),
+        order_id: receipt.order_id,
+        items: Vec::new(),
+        subtotal: 0,
+        discount: 0,
+        tax: 0,
+        service_charge: 0,
+        tip: 0,
+        delivery_fee: 0,
+        total: 0,
+    }).collect();
+
+    match how {
+        // An even split is the same as everyone leaving the amount open
+        Split::Evenly => {
+            let amounts = amounts_for(receipt.total, &vec![None; n])?;
+            share_by_amount(receipt, &amounts, &mut bills);
+        }
+        Split::ByAmount(amounts) => {
+            if amounts.len() != n {
+                return Err(SplitError::WrongPayerCount { payers: n, shares: amounts.len() });
+            }
+            let amounts = amounts_for(receipt.total, amounts)?;
+            share_by_amount(receipt, &amounts, &mut bills);
+        }
+        Split::ByItem(claims) => {
+            if claims.len() != n {
+                return Err(SplitError::WrongPayerCount { payers: n, shares: claims.len() });
+            }
+            if let Some(bad) = claims.iter().flatten()
+                .find(|i| **i == 0 || **i > receipt.items.len()) {
+                return Err(SplitError::UnknownItem(*bad));
+            }
+            for item in 0..receipt.items.len() {
+                let sharers: Vec<usize> = (0..n)
+                    .filter(|p| claims[*p].contains(&(item + 1)))
+                    .collect();
+                if sharers.is_empty() {
+                    return Err(SplitError::ItemNotClaimed(item + 1));
+                }
+                share_item(receipt, item, &sharers, &mut bills);
+            }
+            share_by_items(receipt, &mut bills);
+        }
+    }
+    Ok(bills)
+}
+
+fn total_discount(receipt: &Receipt) -> u32 {
+    receipt.discounts.iter().map(|d| d.amount).sum::<u32>().min(receipt.subtotal)
+}
+
+// Give a pizza to one or more payers
+fn share_item(receipt: &Receipt, item: usize, sharers: &[usize], bills: &mut [PayerReceipt]) {
+    let line = &receipt.items[item];
+    let shares = allocate(line.amount, &vec![1; sharers.len()]);
+    for (payer, amount) in sharers.iter().zip(shares) {
+        bills[*payer].items.push(ItemShare {
+            description: line.description.clone(),
+            amount,
+            shared_by: sharers.len(),
+        });
+        bills[*payer].subtotal += amount;
+    }
+}
+
+// Everything but the pizzas is shared in proportion to what each
+// payer ate. A share of the discount can't be more than the payer's
+// subtotal because the discount is never more than the whole subtotal
+fn share_by_items(receipt: &Receipt, bills: &mut [PayerReceipt]) {
+    let weights: Vec<u32> = bills.iter().map(|b| b.subtotal).collect();
+    let discounts = allocate(total_discount(receipt), &weights);
+    let taxes = allocate(receipt.tax, &weights);
+    let services = allocate(receipt.service_charge, &weights);
+    let tips = allocate(receipt.tip, &weights);
+    let deliveries = allocate(receipt.delivery_fee, &weights);
+
+    for (i, bill) in bills.iter_mut().enumerate() {
+        bill.discount = discounts[i];
+        bill.tax = taxes[i];
+        bill.service_charge = services[i];
+        bill.tip = tips[i];
+        bill.delivery_fee = deliveries[i];
+        bill.total = bill.subtotal - bill.discount + bill.tax + bill.service_charge
+            + bill.tip + bill.delivery_fee;
+    }
+}
+
+// Fill in the amounts nobody gave by sharing what is left evenly
+fn amounts_for(total: u32, amounts: &[Option<u32>]) -> Result<Vec<u32>, SplitError> {
+    let given: u32 = amounts.iter().flatten().sum();
+    let open = amounts.iter().filter(|a| a.is_none()).count();
+    if given > total || (open == 0 && given != total) {
+        return Err(SplitError::AmountsDontAddUp { total, given });
+    }
+    let mut rest = allocate(total - given, &vec![1; open]).into_iter();
+    Ok(amounts.iter().map(|a| a.unwrap_or_else(|| rest.next().unwrap_or(0))).collect())
+}
+
+// Each payer pays exactly their amount. The parts of the bill are
+// shared in proportion and the subtotal is whatever makes the
+// payer's receipt add up, so cents lost to rounding end up there
+fn share_by_amount(receipt: &Receipt, amounts: &[u32], bills: &mut [PayerReceipt]) {
+    let discounts = allocate(total_discount(receipt), amounts);
+    let taxes = allocate(receipt.tax, amounts);
+    let services = allocate(receipt.service_charge, amounts);
+    let tips = allocate(receipt.tip, amounts);
+    let deliveries = allocate(receipt.delivery_fee, amounts);
+
+    for (i, bill) in bills.iter_mut().enumerate() {
+        bill.discount = discounts[i];
+        bill.tax = taxes[i];
+        bill.service_charge = services[i];
+        bill.tip = tips[i];
+        bill.delivery_fee = deliveries[i];
+        let others = bill.tax + bill.service_charge + bill.tip + bill.delivery_fee;
+        bill.subtotal = (amounts[i] + bill.discount).saturating_sub(others);
+        bill.total = amounts[i];
+    }
+}
+
+impl PayerReceipt {
+    // Printed the same width as the full receipt
+    pub fn to_text(&self) -> String {
+        let width = 40;
+        let rule = "-".repeat(width);
+        let row = |label: &str, amount: String| {
+            format!("{:<w$}{:>10}", label, amount, w = width - 10)
+        };
+
+        let mut lines = vec![format!("Order {}  {}", self.order_id, self.payer), rule.clone()];
+        for item in &self.items {
+            lines.push(item.description.clone());
+            let label = if item.shared_by > 1 {
+                format!("  Your share (1 of {})", item.shared_by)
+            } else {
+                String::from("  Pizza total")
+            };
+            lines.push(row(&label, format_price(item.amount)));
+        }
+        if !self.items.is_empty() {
+            lines.push(rule.clone());
+        }
+        lines.push(row("Subtotal", format_price(self.subtotal)));
+        if self.discount > 0 {
+            lines.push(row("Discounts", format!("-{}", format_price(self.discount))));
+        }
+        lines.push(row("Tax", format_price(self.tax)));
+        for (label, amount) in [("Service", self.service_charge), ("Delivery", self.delivery_fee),
+                                ("Tip", self.tip)] {
+            if amount > 0 {
+                lines.push(row(label, format_price(amount)));
+            }
+        }
+        lines.push(row("Your total", format_price(self.total)));
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("payer", Json::string(&self.payer)),
+            ("order_id", Json::from(self.order_id)),
+            ("items", Json::Array(self.items.iter().map(|i| Json::object(vec![
+                ("description", Json::string(&i.description)),
+                ("amount", Json::from(i.amount)),
+                ("shared_by", Json::from(i.shared_by as u32)),
+            ])).collect())),
+            ("subtotal", Json::from(self.subtotal)),
+            ("discount", Json::from(self.discount)),
+            ("tax", Json::from(self.tax)),
+            ("service_charge", Json::from(self.service_charge)),
+            ("tip", Json::from(self.tip)),
+            ("delivery_fee", Json::from(self.delivery_fee)),
+            ("total", Json::from(self.total)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::receipt::{Adjustment, LineItem, ReceiptConfig, Tip};
+    use super::*;
+
+    // Amounts that don't divide evenly so rounding has to be handled
+    fn receipt() -> Receipt {
+        let items: Vec<LineItem> = [1001, 1333, 777].iter().enumerate().map(|(i, amount)| LineItem {
+            description: format!("pizza {}", i + 1),
+            components: Vec::new(),
+            amount: *amount,
+        }).collect();
+        let subtotal = items.iter().map(|i| i.amount).sum();
+        let discounts = vec![Adjustment { name: String::from("Lunch special"), amount: 250 }];
+        Receipt::from_items(7, Some(3), items, subtotal, discounts, &ReceiptConfig::standard(),
+            Tip::Percent(1_800)).with_delivery_fee(199)
+    }
+
+    // Every part of the bill adds up across the payers
+    fn assert_adds_up(receipt: &Receipt, bills: &[PayerReceipt]) {
+        let sum = |part: fn(&PayerReceipt) -> u32| bills.iter().map(part).sum::<u32>();
+        assert_eq!(sum(|b| b.total), receipt.total);
+        assert_eq!(sum(|b| b.discount), receipt.discounts.iter().map(|d| d.amount).sum());
+        assert_eq!(sum(|b| b.tax), receipt.tax);
+        assert_eq!(sum(|b| b.service_charge), receipt.service_charge);
+        assert_eq!(sum(|b| b.tip), receipt.tip);
+        assert_eq!(sum(|b| b.delivery_fee), receipt.delivery_fee);
+        for bill in bills {
+            assert_eq!(bill.total, bill.subtotal - bill.discount + bill.tax + bill.service_charge
+                + bill.tip + bill.delivery_fee);
+        }
+    }
+
+    #[test]
+    fn allocate_hands_out_every_cent() {
+        assert_eq!(allocate(100, &[1, 1, 1]), vec![34, 33, 33]);
+        assert_eq!(allocate(10, &[0, 0]), vec![5, 5]);
+        assert_eq!(allocate(0, &[3, 4]), vec![0, 0]);
+        assert!(allocate(5, &[]).is_empty());
+        for amount in [1, 99, 1_001, 123_457] {
+            assert_eq!(allocate(amount, &[7, 3, 11, 1]).iter().sum::<u32>(), amount);
+        }
+    }
+
+    #[test]
+    fn even_split_sums_to_the_total() {
+        let receipt = receipt();
+        for payers in [vec!["Bob"], vec!["Bob", "Jane"], vec!["Bob", "Jane", "Sam"]] {
+            let bills = split(&receipt, &payers, &Split::Evenly).unwrap();
+            assert_adds_up(&receipt, &bills);
+            let most = bills.iter().map(|b| b.total).max().unwrap();
+            let least = bills.iter().map(|b| b.total).min().unwrap();
+            assert!(most - least <= 1);
+        }
+    }
+
+    #[test]
+    fn split_by_item_shares_pizzas_and_sums_to_the_total() {
+        let receipt = receipt();
+        let how = Split::ByItem(vec![vec![1, 2], vec![2], vec![2, 3]]);
+        let bills = split(&receipt, &["Bob", "Jane", "Sam"], &how).unwrap();
+        assert_adds_up(&receipt, &bills);
+        let shared: Vec<u32> = bills.iter()
+            .flat_map(|b| b.items.iter().filter(|i| i.description == "pizza 2"))
+            .map(|i| i.amount)
+            .collect();
+        assert_eq!(shared, vec![445, 444, 444]);
+    }
+
+    #[test]
+    fn split_by_amount_pays_exactly_what_was_asked() {
+        let receipt = receipt();
+        let how = Split::ByAmount(vec![Some(1_000), None, None]);
+        let bills = split(&receipt, &["Bob", "Jane", "Sam"], &how).unwrap();
+        assert_adds_up(&receipt, &bills);
+        assert_eq!(bills[0].total, 1_000);
+    }
+
+    #[test]
+    fn bad_splits_are_refused() {
+        let receipt = receipt();
+        assert_eq!(split(&receipt, &[], &Split::Evenly), Err(SplitError::NoPayers));
+        assert_eq!(split(&receipt, &["Bob", "Jane"], &Split::ByItem(vec![vec![1, 2], vec![4]])),
+            Err(SplitError::UnknownItem(4)));
+        assert_eq!(split(&receipt, &["Bob", "Jane"], &Split::ByItem(vec![vec![1], vec![2]])),
+            Err(SplitError::ItemNotClaimed(3)));
+        let too_much = Split::ByAmount(vec![Some(receipt.total), Some(1)]);
+        assert_eq!(split(&receipt, &["Bob", "Jane"], &too_much),
+            Err(SplitError::AmountsDontAddUp { total: receipt.total, given: receipt.total + 1 }));
+    }
+}
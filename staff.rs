@@ -0,0 +1,425 @@
+// The staff who run the restaurant. Everyone is hired for one or
+// more roles and the roster says when they work each day of the
+// week. Orders go to whoever is on shift in the role that is needed
+// and has the fewest orders so far, so the work is shared out
+// Time cards record when people actually worked which is what they
+// are paid for
+
+// Shifts repeat every week and can't run past midnight. Times are
+// minutes since midnight in UTC like everywhere else
+
+use super::date::{self, Date, Day};
+use super::menu::format_price;
+use std::fmt;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    // Greets and seats parties
+    Host,
+    Cook,
+    // Takes orders and looks after the table
+    Server,
+    Driver,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Host, Role::Cook, Role::Server, Role::Driver];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Host => "host",
+            Role::Cook => "cook",
+            Role::Server => "server",
+            Role::Driver => "driver",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|r| r.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaffError {
+    EmptyName,
+    NoRoles,
+    UnknownEmployee(u32),
+    CantWorkRole { name: String, role: Role },
+    // The end has to be after the start on the same day
+    BadShift { start: u32, end: u32 },
+    ShiftOverlaps { name: String, day: Day },
+    NobodyOnShift(Role),
+    AlreadyClockedIn(String),
+    NotClockedIn(String),
+}
+
+impl fmt::Display for StaffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StaffError::EmptyName => write!(f, "Staff need a name"),
+            StaffError::NoRoles => write!(f, "Staff need at least one role"),
+            StaffError::UnknownEmployee(id) => write!(f, "There is no employee {}", id),
+            StaffError::CantWorkRole { name, role } =>
+                write!(f, "{} isn't hired to work as a {}", name, role.name()),
+            StaffError::BadShift { start, end } =>
+                write!(f, "A shift from {} to {} has to end later the same day",
+                    date::format_time_of_day(*start), date::format_time_of_day(*end)),
+            StaffError::ShiftOverlaps { name, day } =>
+                write!(f, "{} already works then on {}", name, day),
+            StaffError::NobodyOnShift(role) => write!(f, "No {} is on shift", role.name()),
+            StaffError::AlreadyClockedIn(name) => write!(f, "{} is already clocked in", name),
+            StaffError::NotClockedIn(name) => write!(f, "{} isn't clocked in", name),
+        }
+    }
+}
+
+impl std::error::Error for StaffError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Employee {
+    pub id: u32,
+    pub name: String,
+    pub roles: Vec<Role>,
+    // Cents per hour
+    pub hourly_rate: u32,
+}
+
+impl Employee {
+    pub fn can_work(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+}
+
+// One block of work that happens every week on the same day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shift {
+    pub employee_id: u32,
+    pub role: Role,
+    pub day: Day,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Shift {
+    pub fn minutes(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn covers(&self, day: Day, minute: u32) -> bool {
+        self.day == day && (self.start..self.end).contains(&minute)
+    }
+}
+
+// Who was given an order and in which role
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Assignment {
+    pub order_id: u32,
+    pub role: Role,
+    pub employee_id: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeCard {
+    pub employee_id: u32,
+    pub clock_in: SystemTime,
+    // None while they are still working
+    pub clock_out: Option<SystemTime>,
+}
+
+// What one employee worked between two dates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hours {
+    pub employee_id: u32,
+    pub name: String,
+    pub scheduled_minutes: u32,
+    pub worked_minutes: u32,
+    pub orders: u32,
+    // Cents for the minutes worked
+    pub pay: u32,
+}
+
+pub struct Roster {
+    employees: Vec<Employee>,
+    shifts: Vec<Shift>,
+    assignments: Vec<Assignment>,
+    cards: Vec<TimeCard>,
+}
+
+impl Roster {
+    pub fn new() -> Roster {
+        Roster {
+            employees: Vec::new(),
+            shifts: Vec::new(),
+            assignments: Vec::new(),
+            cards: Vec::new(),
+        }
+    }
+
+    // Our team : a day crew from 10:30 and an evening crew from
+    // 16:30 until after closing, every day of the week
+    pub fn standard() -> Roster {
+        let mut roster = Roster::new();
+        let team = [
+            ("Hannah", vec![Role::Host], 1_600, 0),
+            ("Carlos", vec![Role::Cook], 2_000, 0),
+            ("Sam", vec![Role::Server, Role::Host], 1_500, 0),
+            ("Dana", vec![Role::Driver], 1_700, 0),
+            ("Omar", vec![Role::Host, Role::Server], 1_600, 1),
+            ("Priya", vec![Role::Cook], 2_100, 1),
+            ("Alex", vec![Role::Server], 1_500, 1),
+            ("Lee", vec![Role::Driver], 1_700, 1),
+        ];
+        let crews = [(10 * 60 + 30, 16 * 60 + 30), (16 * 60 + 30, 23 * 60)];
+        // The names, roles and times above are all valid so none
+        // of this can fail
+        for (name, roles, rate, crew) in team {
+            let role = roles[0];
+            let id = roster.hire(name, &roles, rate).unwrap();
+            let (start, end) = crews[crew];
+            for day in Day::ALL {
+                roster.schedule(id, role, day, start, end).unwrap();
+            }
+        }
+        roster
+    }
+
+    // Returns the new employee's id
+    pub fn hire(&mut self, name: &str, roles: &[Role], hourly_rate: u32) -> Result<u32, StaffError> {
+        if name.trim().is_empty() {
+            return Err(StaffError::EmptyName);
+        }
+        if roles.is_empty() {
+            return Err(StaffError::NoRoles);
+        }
+        let id = self.employees.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        self.employees.push(Employee {
+            id,
+            name: String::from(name.trim()),
+            roles: roles.to_vec(),
+            hourly_rate,
+        });
+        Ok(id)
+    }
+
+    pub fn employees(&self) -> &[Employee] {
+        &self.employees
+    }
+
+    pub fn employee(&self, id: u32) -> Option<&Employee> {
+        self.employees.iter().find(|e| e.id == id)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Employee> {
+        self.employees.iter().find(|e| e.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn get(&self, id: u32) -> Result<&Employee, StaffError> {
+        self.employee(id).ok_or(StaffError::UnknownEmployee(id))
+    }
+
+    // Put someone on the roster every week on a day
+    pub fn schedule(&mut self, employee_id: u32, role: Role, day: Day, start: u32, end: u32)
+                    -> Result<(), StaffError> {
+        let employee = self.get(employee_id)?;
+        if !employee.can_work(role) {
+            return Err(StaffError::CantWorkRole { name: employee.name.clone(), role });
+        }
+        if start >= end || end > 24 * 60 {
+            return Err(StaffError::BadShift { start, end });
+        }
+        let overlaps = self.shifts.iter().any(|s| {
+            s.employee_id == employee_id && s.day == day && s.start < end && start < s.end
+        });
+        if overlaps {
+            return Err(StaffError::ShiftOverlaps { name: employee.name.clone(), day });
+        }
+        self.shifts.push(Shift { employee_id, role, day, start, end });
+        Ok(())
+    }
+
+    // Take someone off the roster for a day. Returns how many
+    // shifts were removed
+    pub fn unschedule(&mut self, employee_id: u32, day: Day) -> Result<usize, StaffError> {
+        self.get(employee_id)?;
+        let before = self.shifts.len();
+        self.shifts.retain(|s| !(s.employee_id == employee_id && s.day == day));
+        Ok(before - self.shifts.len())
+    }
+
+    // Every shift on a day from the earliest start
+    pub fn shifts_on(&self, day: Day) -> Vec<&Shift> {
+        let mut shifts: Vec<&Shift> = self.shifts.iter().filter(|s| s.day == day).collect();
+        shifts.sort_by_key(|s| (s.start, s.role.name(), s.employee_id));
+        shifts
+    }
+
+    // Who is working in a role at a moment
+    pub fn on_shift(&self, role: Role, at: SystemTime) -> Vec<&Employee> {
+        let day = Date::of(at).day_of_week();
+        let minute = date::minute_of_day(at);
+        self.employees.iter()
+            .filter(|e| self.shifts.iter().any(|s| {
+                s.employee_id == e.id && s.role == role && s.covers(day, minute)
+            }))
+            .collect()
+    }
+
+    // Give an order to whoever in the role has had the fewest. An
+    // order already given to someone in the role stays with them
+    pub fn assign(&mut self, order_id: u32, role: Role, at: SystemTime)
+                  -> Result<&Employee, StaffError> {
+        if let Some(a) = self.assignments.iter().find(|a| a.order_id == order_id && a.role == role) {
+            return self.get(a.employee_id);
+        }
+        let employee_id = self.on_shift(role, at).into_iter()
+            .min_by_key(|e| (self.orders_for(e.id, role), e.id))
+            .ok_or(StaffError::NobodyOnShift(role))?
+            .id;
+        self.assignments.push(Assignment { order_id, role, employee_id });
+        self.get(employee_id)
+    }
+
+    // Give an order to someone picked another way, like the driver
+    // dispatch sent. They don't have to be on shift since people
+    // get called in
+    pub fn assign_to(&mut self, order_id: u32, role: Role, employee_id: u32)
+                     -> Result<&Employee, StaffError> {
+        let employee = self.get(employee_id)?;
+        if !employee.can_work(role) {
+            return Err(StaffError::CantWorkRole { name: employee.name.clone(), role });
+        }
+        if let Some(a) = self.assignments.iter().find(|a| a.order_id == order_id && a.role == role) {
+            return self.get(a.employee_id);
+        }
+        self.assignments.push(Assignment { order_id, role, employee_id });
+        self.get(employee_id)
+    }
+
+    // Take an order back from whoever in the role had it, like when
+    // the kitchen didn't take the order after all
+    pub fn unassign(&mut self, order_id: u32, role: Role) {
+        self.assignments.retain(|a| a.order_id != order_id || a.role != role);
+    }
+
+    // Everyone who worked on an order
+    pub fn assigned(&self, order_id: u32) -> Vec<(Role, &Employee)> {
+        self.assignments.iter()
+            .filter(|a| a.order_id == order_id)
+            .filter_map(|a| Some((a.role, self.employee(a.employee_id)?)))
+            .collect()
+    }
+
+    // The same by name to be kept with the order
+    pub fn names(&self, order_id: u32) -> Vec<(Role, String)> {
+        self.assigned(order_id).into_iter().map(|(role, e)| (role, e.name.clone())).collect()
+    }
+
+    fn orders_for(&self, employee_id: u32, role: Role) -> usize {
+        self.assignments.iter().filter(|a| a.employee_id == employee_id && a.role == role).count()
+    }
+
+    pub fn clock_in(&mut self, employee_id: u32, at: SystemTime) -> Result<(), StaffError> {
+        let name = self.get(employee_id)?.name.clone();
+        if self.open_card(employee_id).is_some() {
+            return Err(StaffError::AlreadyClockedIn(name));
+        }
+        self.cards.push(TimeCard { employee_id, clock_in: at, clock_out: None });
+        Ok(())
+    }
+
+    // Clocking out before the time they clocked in counts as no time
+    pub fn clock_out(&mut self, employee_id: u32, at: SystemTime) -> Result<(), StaffError> {
+        let name = self.get(employee_id)?.name.clone();
+        let card = self.open_card(employee_id).ok_or(StaffError::NotClockedIn(name))?;
+        card.clock_out = Some(at.max(card.clock_in));
+        Ok(())
+    }
+
+    fn open_card(&mut self, employee_id: u32) -> Option<&mut TimeCard> {
+        self.cards.iter_mut().find(|c| c.employee_id == employee_id && c.clock_out.is_none())
+    }
+
+    pub fn time_cards(&self) -> &[TimeCard] {
+        &self.cards
+    }
+
+    // Hours for everyone from the first day to the last, both
+    // included. Only finished time cards count and the part of a
+    // card outside the days is left out
+    pub fn hours(&self, first: Date, last: Date) -> Vec<Hours> {
+        let from = date::millis(first.start());
+        let to = date::millis(last.next().start());
+        let mut days = Vec::new();
+        let mut day = first;
+        while day <= last {
+            days.push(day.day_of_week());
+            day = day.next();
+        }
+
+        self.employees.iter().map(|e| {
+            let scheduled_minutes = days.iter()
+                .flat_map(|day| self.shifts.iter().filter(move |s| s.employee_id == e.id && s.day == *day))
+                .map(|s| s.minutes())
+                .sum();
+            let worked_ms: i64 = self.cards.iter()
+                .filter(|c| c.employee_id == e.id)
+                .filter_map(|c| Some((date::millis(c.clock_in), date::millis(c.clock_out?))))
+                .map(|(start, end)| (end.min(to) - start.max(from)).max(0))
+                .sum();
+            let worked_minutes = (worked_ms / 60_000) as u32;
+            let pay = (worked_minutes as u64 * e.hourly_rate as u64 + 30) / 60;
+            Hours {
+                employee_id: e.id,
+                name: e.name.clone(),
+                scheduled_minutes,
+                worked_minutes,
+                orders: self.assignments.iter().filter(|a| a.employee_id == e.id).count() as u32,
+                pay: pay as u32,
+            }
+        }).collect()
+    }
+
+    // The hours as a table for the manager
+    pub fn hours_report(&self, first: Date, last: Date) -> String {
+        let hours = self.hours(first, last);
+        let mut lines = vec![
+            format!("Hours from {} to {}", first, last),
+            format!("{:<12}{:>10}{:>10}{:>8}{:>10}", "Name", "Scheduled", "Worked", "Orders", "Pay"),
+        ];
+        for h in &hours {
+            lines.push(format!("{:<12}{:>10}{:>10}{:>8}{:>10}", h.name,
+                format_hours(h.scheduled_minutes), format_hours(h.worked_minutes), h.orders,
+                format_price(h.pay)));
+        }
+        lines.push(format!("{:<12}{:>10}{:>10}{:>8}{:>10}", "Total",
+            format_hours(hours.iter().map(|h| h.scheduled_minutes).sum()),
+            format_hours(hours.iter().map(|h| h.worked_minutes).sum()),
+            hours.iter().map(|h| h.orders).sum::<u32>(),
+            format_price(hours.iter().map(|h| h.pay).sum())));
+        lines.join("\n")
+    }
+
+    // Who works when for a whole week
+    pub fn week_sheet(&self) -> String {
+        let mut lines = Vec::new();
+        for day in Day::ALL {
+            lines.push(day.name().to_string());
+            let shifts = self.shifts_on(day);
+            if shifts.is_empty() {
+                lines.push(String::from("  Nobody is working"));
+            }
+            for shift in shifts {
+                let name = self.employee(shift.employee_id).map_or("?", |e| e.name.as_str());
+                lines.push(format!("  {}-{}  {:<8}{}", date::format_time_of_day(shift.start),
+                    date::format_time_of_day(shift.end), shift.role.name(), name));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+// Minutes as hours and minutes like 7:05
+pub fn format_hours(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
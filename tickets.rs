@@ -0,0 +1,152 @@
+// A ticket is what the cooks work from. It prints when a cook
+// starts on an order so it has any changes made while the order
+// waited. It shows what goes on each pizza with the modifiers,
+// the customer's allergies and where the food is going
+
+// Tickets can be plain text or ESC/POS, the commands thermal
+// printers understand. Either is written to a path which can be a
+// file or the printer's device like /dev/usb/lp0
+
+use super::date;
+use super::dietary;
+use super::order::Order;
+use super::pizza_order::Modifier;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Characters across a 58mm roll
+pub const WIDTH: usize = 32;
+
+// ESC/POS commands
+const INIT: [u8; 2] = [0x1B, b'@'];
+const BOLD_ON: [u8; 3] = [0x1B, b'E', 1];
+const BOLD_OFF: [u8; 3] = [0x1B, b'E', 0];
+// Double width and double height
+const BIG_ON: [u8; 3] = [0x1D, b'!', 0x11];
+const BIG_OFF: [u8; 3] = [0x1D, b'!', 0x00];
+// Feed 4 lines so the cut misses the last line then cut most of
+// the way through so the ticket doesn't fall on the floor
+const FEED: [u8; 3] = [0x1B, b'd', 4];
+const CUT: [u8; 4] = [0x1D, b'V', 66, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Normal,
+    // Allergies and anything the cook could miss
+    Bold,
+    // The order number and where it goes
+    Big,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ticket {
+    pub lines: Vec<(Style, String)>,
+}
+
+impl Ticket {
+    pub fn for_order(order: &Order) -> Ticket {
+        let rule = "-".repeat(WIDTH);
+        let mut lines = vec![(Style::Big, format!("ORDER {}", order.id))];
+        let going = match (order.table, &order.delivery) {
+            (Some(table), _) => format!("TABLE {}", table),
+            (None, Some(_)) => String::from("DELIVERY"),
+            (None, None) => String::from("PICK UP"),
+        };
+        lines.push((Style::Big, going));
+
+        let placed = date::format_time_of_day(date::minute_of_day(order.placed_at()));
+        let mut heading = format!("Placed {}", placed);
+        if let Some(customer) = &order.customer {
+            heading.push_str(&format!("  {}", customer));
+        }
+        lines.push((Style::Normal, heading));
+
+        if !order.needs.allergies.is_empty() {
+            let names: Vec<&str> = order.needs.allergies.iter().map(|a| a.name()).collect();
+            lines.push((Style::Bold, format!("!! ALLERGY : {}", names.join(", ").to_uppercase())));
+        }
+        if !order.needs.diets.is_empty() {
+            let names: Vec<&str> = order.needs.diets.iter().map(|d| d.name()).collect();
+            lines.push((Style::Normal, format!("Diet : {}", names.join(", "))));
+        }
+        lines.push((Style::Normal, rule.clone()));
+
+        for (i, pizza) in order.pizzas.iter().enumerate() {
+            lines.push((Style::Bold, format!("{}  {} {}", i + 1, pizza.size.name, pizza.crust.name)));
+            for part in std::iter::once(&pizza.cheese).chain(pizza.toppings.iter()) {
+                if !pizza.has(&Modifier::No(part.name.clone())) {
+                    lines.push((Style::Normal, format!("     {}", part.name)));
+                }
+            }
+            for modifier in &pizza.modifiers {
+                lines.push((Style::Bold, format!("   > {}", modifier.to_string().to_uppercase())));
+            }
+            // Diet problems were accepted when the order was taken
+            // but the cook should still know
+            for conflict in dietary::check(pizza, &order.needs) {
+                lines.push((Style::Bold, format!("   !! {}", conflict)));
+            }
+        }
+        lines.push((Style::Normal, rule));
+
+        let count = order.pizzas.len();
+        lines.push((Style::Normal, format!("{} pizza{}", count, if count == 1 { "" } else { "s" })));
+        Ticket { lines }
+    }
+
+    pub fn to_text(&self) -> String {
+        let lines: Vec<&str> = self.lines.iter().map(|(_, text)| text.as_str()).collect();
+        lines.join("\n")
+    }
+
+    // The bytes to send to a thermal printer. Printers only know
+    // plain ASCII so anything else prints as ?
+    pub fn to_escpos(&self) -> Vec<u8> {
+        let mut bytes = INIT.to_vec();
+        for (style, text) in &self.lines {
+            let (on, off) = match style {
+                Style::Normal => (&[][..], &[][..]),
+                Style::Bold => (&BOLD_ON[..], &BOLD_OFF[..]),
+                Style::Big => (&BIG_ON[..], &BIG_OFF[..]),
+            };
+            bytes.extend_from_slice(on);
+            bytes.extend(text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }));
+            bytes.push(b'\n');
+            bytes.extend_from_slice(off);
+        }
+        bytes.extend_from_slice(&FEED);
+        bytes.extend_from_slice(&CUT);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TicketFormat {
+    Text,
+    EscPos,
+}
+
+// Where tickets go. Each ticket is added to the end so a file
+// keeps every ticket printed
+#[derive(Debug, Clone)]
+pub struct TicketPrinter {
+    path: PathBuf,
+    format: TicketFormat,
+}
+
+impl TicketPrinter {
+    pub fn new<P: AsRef<Path>>(path: P, format: TicketFormat) -> TicketPrinter {
+        TicketPrinter { path: path.as_ref().to_path_buf(), format }
+    }
+
+    pub fn print(&self, ticket: &Ticket) -> io::Result<()> {
+        let bytes = match self.format {
+            TicketFormat::Text => format!("{}\n\n", ticket.to_text()).into_bytes(),
+            TicketFormat::EscPos => ticket.to_escpos(),
+        };
+        let mut out = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        out.write_all(&bytes)?;
+        out.flush()
+    }
+}
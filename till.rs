@@ -0,0 +1,387 @@
+// The till records the money side of every order : what was paid
+// and every change after that. Nothing is ever edited or removed.
+// A refund is a new entry linked to the payment it gives money back
+// from so the payments minus the refunds always add up to what is
+// in the drawer. Like the loyalty ledger it is a JSON lines file
+
+// Orders can be changed before the kitchen starts on them, voided
+// any time before they are paid and refunded in full or in part
+// after. Voids and refunds always need a reason
+
+use super::date::{self, Date};
+use super::json::Json;
+use super::menu::format_price;
+use super::order::{self, Order, OrderError};
+use super::receipt::Receipt;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Why money was taken off an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    WrongOrder,
+    Quality,
+    LongWait,
+    ChangedMind,
+    Duplicate,
+    OnTheHouse,
+    Other,
+}
+
+impl Reason {
+    pub const ALL: [Reason; 7] = [Reason::WrongOrder, Reason::Quality, Reason::LongWait,
+        Reason::ChangedMind, Reason::Duplicate, Reason::OnTheHouse, Reason::Other];
+
+    // The code staff type and the file stores
+    pub fn code(&self) -> &'static str {
+        match self {
+            Reason::WrongOrder => "wrong-order",
+            Reason::Quality => "quality",
+            Reason::LongWait => "long-wait",
+            Reason::ChangedMind => "changed-mind",
+            Reason::Duplicate => "duplicate",
+            Reason::OnTheHouse => "on-the-house",
+            Reason::Other => "other",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Reason> {
+        Reason::ALL.into_iter().find(|r| r.code().eq_ignore_ascii_case(code.trim()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Paid,
+    // Pizzas were changed before the order was made
+    Modified,
+    Voided,
+    Refunded,
+    // A void taken back because the order couldn't be stopped
+    Reinstated,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 5] = [EntryKind::Paid, EntryKind::Modified, EntryKind::Voided,
+        EntryKind::Refunded, EntryKind::Reinstated];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryKind::Paid => "Paid",
+            EntryKind::Modified => "Modified",
+            EntryKind::Voided => "Voided",
+            EntryKind::Refunded => "Refunded",
+            EntryKind::Reinstated => "Reinstated",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EntryKind> {
+        EntryKind::ALL.into_iter().find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug)]
+pub enum TillError {
+    Io(io::Error),
+    BadLine { line: usize, message: String },
+    NotPaid(u32),
+    NotVoided(u32),
+    AlreadyPaid(u32),
+    AlreadyVoided(u32),
+    NothingToRefund(u32),
+    TooMuch { order_id: u32, wanted: u32, refundable: u32 },
+    ZeroAmount,
+    Order(u32, OrderError),
+}
+
+impl fmt::Display for TillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TillError::Io(e) => write!(f, "Till file error : {}", e),
+            TillError::BadLine { line, message } => write!(f, "Till line {} : {}", line, message),
+            TillError::NotPaid(id) => write!(f, "Order {} hasn't been paid", id),
+            TillError::NotVoided(id) => write!(f, "Order {} wasn't voided", id),
+            TillError::AlreadyPaid(id) =>
+                write!(f, "Order {} is already paid, give a refund instead", id),
+            TillError::AlreadyVoided(id) => write!(f, "Order {} was voided", id),
+            TillError::NothingToRefund(id) => write!(f, "Order {} was already refunded in full", id),
+            TillError::TooMuch { order_id, wanted, refundable } =>
+                write!(f, "Can't refund {} on order {}, only {} is left to refund",
+                    format_price(*wanted), order_id, format_price(*refundable)),
+            TillError::ZeroAmount => write!(f, "A refund needs an amount"),
+            TillError::Order(id, e) => write!(f, "Order {} : {}", id, e),
+        }
+    }
+}
+
+impl std::error::Error for TillError {}
+
+impl From<io::Error> for TillError {
+    fn from(e: io::Error) -> TillError {
+        TillError::Io(e)
+    }
+}
+
+// One change to the money for an order
+#[derive(Debug, Clone, PartialEq)]
+pub struct TillEntry {
+    // Numbered from 1 in the order they were made
+    pub id: u32,
+    pub order_id: u32,
+    pub at: SystemTime,
+    pub kind: EntryKind,
+    // Cents, negative when the bill went down or money went back
+    pub amount: i64,
+    // Payments don't need one
+    pub reason: Option<Reason>,
+    // The entry this one changes, a refund links to its payment
+    pub linked: Option<u32>,
+    pub note: String,
+}
+
+impl TillEntry {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("id", Json::from(self.id)),
+            ("order_id", Json::from(self.order_id)),
+            ("at", Json::from(date::millis(self.at))),
+            ("kind", Json::string(self.kind.name())),
+            ("amount", Json::from(self.amount)),
+            ("reason", self.reason.map_or(Json::Null, |r| Json::string(r.code()))),
+            ("linked", Json::from(self.linked)),
+            ("note", Json::string(&self.note)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<TillEntry> {
+        let reason = match json.get("reason").and_then(|r| r.as_str()) {
+            Some(code) => Some(Reason::from_code(code)?),
+            None => None,
+        };
+        Some(TillEntry {
+            id: json.get("id")?.as_u32()?,
+            order_id: json.get("order_id")?.as_u32()?,
+            at: date::from_millis(json.get("at")?.as_i64()?),
+            kind: EntryKind::from_name(json.get("kind")?.as_str()?)?,
+            amount: json.get("amount")?.as_i64()?,
+            reason,
+            linked: json.get("linked").and_then(|l| l.as_u32()),
+            note: String::from(json.get("note").and_then(|n| n.as_str()).unwrap_or("")),
+        })
+    }
+}
+
+impl fmt::Display for TillEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let amount = format!("{}{}", sign, format_price(self.amount.unsigned_abs() as u32));
+        let linked = self.linked.map_or(String::new(), |l| format!("#{}", l));
+        let line = format!("#{:<4}{:<7}order {:<5}{:<10}{:>10}  {:<5}{:<14}{}",
+            self.id, date::format_time_of_day(date::minute_of_day(self.at)), self.order_id,
+            self.kind.name(), amount, linked, self.reason.map_or("", |r| r.code()), self.note);
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+pub struct Till {
+    // Without a path the till is only kept in memory
+    path: Option<PathBuf>,
+    entries: Vec<TillEntry>,
+}
+
+impl Till {
+    pub fn new() -> Till {
+        Till { path: None, entries: Vec::new() }
+    }
+
+    // Load every entry. A missing file means nothing was paid yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Till, TillError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+
+        let file = match File::open(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(TillError::Io(e)),
+        };
+
+        if let Some(file) = file {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json = Json::parse(&line).map_err(|e| TillError::BadLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                })?;
+                entries.push(TillEntry::from_json(&json).ok_or(TillError::BadLine {
+                    line: i + 1,
+                    message: String::from("Not a valid till entry"),
+                })?);
+            }
+        }
+
+        // Voided orders keep their numbers after a restart too
+        if let Some(max) = entries.iter().map(|e| e.order_id).max() {
+            order::reserve_ids_through(max);
+        }
+
+        Ok(Till { path: Some(path), entries })
+    }
+
+    // Write the entry to the file first so memory never has money
+    // the file doesn't
+    fn record(&mut self, order_id: u32, kind: EntryKind, amount: i64, reason: Option<Reason>,
+              linked: Option<u32>, note: &str) -> Result<&TillEntry, TillError> {
+        let entry = TillEntry {
+            id: self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            order_id,
+            at: SystemTime::now(),
+            kind,
+            amount,
+            reason,
+            linked,
+            note: String::from(note),
+        };
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", entry.to_json())?;
+        }
+        self.entries.push(entry);
+        Ok(&self.entries[self.entries.len() - 1])
+    }
+
+    pub fn entries(&self) -> &[TillEntry] {
+        &self.entries
+    }
+
+    // Every entry for an order oldest first
+    pub fn for_order(&self, order_id: u32) -> Vec<&TillEntry> {
+        self.entries.iter().filter(|e| e.order_id == order_id).collect()
+    }
+
+    fn find(&self, order_id: u32, kind: EntryKind) -> Option<&TillEntry> {
+        self.entries.iter().find(|e| e.order_id == order_id && e.kind == kind)
+    }
+
+    pub fn is_paid(&self, order_id: u32) -> bool {
+        self.find(order_id, EntryKind::Paid).is_some()
+    }
+
+    // Voided and not reinstated since
+    pub fn is_voided(&self, order_id: u32) -> bool {
+        self.entries.iter().rev().find(|e| e.order_id == order_id
+            && matches!(e.kind, EntryKind::Voided | EntryKind::Reinstated))
+            .is_some_and(|e| e.kind == EntryKind::Voided)
+    }
+
+    // Cents given back on an order so far
+    pub fn refunded(&self, order_id: u32) -> u32 {
+        self.entries.iter()
+            .filter(|e| e.order_id == order_id && e.kind == EntryKind::Refunded)
+            .map(|e| e.amount.unsigned_abs() as u32)
+            .sum()
+    }
+
+    // What was paid less what was already refunded
+    pub fn refundable(&self, order_id: u32) -> u32 {
+        let paid = self.find(order_id, EntryKind::Paid).map_or(0, |p| p.amount as u32);
+        paid.saturating_sub(self.refunded(order_id))
+    }
+
+    fn check_open(&self, order_id: u32) -> Result<(), TillError> {
+        if self.is_paid(order_id) {
+            return Err(TillError::AlreadyPaid(order_id));
+        }
+        if self.is_voided(order_id) {
+            return Err(TillError::AlreadyVoided(order_id));
+        }
+        Ok(())
+    }
+
+    // The customer paid the receipt
+    pub fn pay(&mut self, receipt: &Receipt) -> Result<&TillEntry, TillError> {
+        self.check_open(receipt.order_id)?;
+        self.record(receipt.order_id, EntryKind::Paid, receipt.total as i64, None, None, "")
+    }
+
+    // Note a change made to the pizzas before the order was paid
+    // before is what the pizzas cost before the change
+    pub fn modified(&mut self, before: u32, order: &Order, note: &str)
+                    -> Result<&TillEntry, TillError> {
+        self.check_open(order.id)?;
+        let change = order.total() as i64 - before as i64;
+        self.record(order.id, EntryKind::Modified, change, None, None, note)
+    }
+
+    // Take an unpaid order off the books. The order is voided too so
+    // it won't be counted as a sale
+    pub fn void(&mut self, order: &mut Order, reason: Reason, note: &str)
+                -> Result<&TillEntry, TillError> {
+        self.check_open(order.id)?;
+        order.void().map_err(|e| TillError::Order(order.id, e))?;
+        let amount = -(order.total() as i64);
+        self.record(order.id, EntryKind::Voided, amount, Some(reason), None, note)
+    }
+
+    // Put a voided order back on the books. Used when the void
+    // couldn't be carried out anywhere else
+    pub fn reinstate(&mut self, order_id: u32, note: &str) -> Result<&TillEntry, TillError> {
+        if !self.is_voided(order_id) {
+            return Err(TillError::NotVoided(order_id));
+        }
+        let void = self.entries.iter().rev()
+            .find(|e| e.order_id == order_id && e.kind == EntryKind::Voided)
+            .ok_or(TillError::NotVoided(order_id))?;
+        let (amount, linked) = (-void.amount, void.id);
+        self.record(order_id, EntryKind::Reinstated, amount, None, Some(linked), note)
+    }
+
+    // Give money back on a paid order. None refunds everything that
+    // hasn't been refunded yet
+    pub fn refund(&mut self, order_id: u32, amount: Option<u32>, reason: Reason, note: &str)
+                  -> Result<&TillEntry, TillError> {
+        let payment = self.find(order_id, EntryKind::Paid)
+            .ok_or(TillError::NotPaid(order_id))?.id;
+        let refundable = self.refundable(order_id);
+        if refundable == 0 {
+            return Err(TillError::NothingToRefund(order_id));
+        }
+        let wanted = amount.unwrap_or(refundable);
+        if wanted == 0 {
+            return Err(TillError::ZeroAmount);
+        }
+        if wanted > refundable {
+            return Err(TillError::TooMuch { order_id, wanted, refundable });
+        }
+        self.record(order_id, EntryKind::Refunded, -(wanted as i64), Some(reason), Some(payment),
+            note)
+    }
+
+    // Every entry on a day and what is in the drawer at the end
+    pub fn day_sheet(&self, day: Date) -> String {
+        let entries: Vec<&TillEntry> = self.entries.iter()
+            .filter(|e| Date::of(e.at) == day)
+            .collect();
+        let total = |kind: EntryKind| -> u32 {
+            entries.iter().filter(|e| e.kind == kind).map(|e| e.amount.unsigned_abs() as u32).sum()
+        };
+        let taken = total(EntryKind::Paid);
+        let refunded = total(EntryKind::Refunded);
+        let voided = total(EntryKind::Voided).saturating_sub(total(EntryKind::Reinstated));
+
+        let mut lines = vec![format!("Till for {}", day)];
+        lines.extend(entries.iter().map(|e| e.to_string()));
+        if entries.is_empty() {
+            lines.push(String::from("Nothing taken"));
+        }
+        lines.push(format!("Taken {}  Refunded {}  Voided {}  In the drawer {}",
+            format_price(taken), format_price(refunded), format_price(voided),
+            format_price(taken.saturating_sub(refunded))));
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,462 @@
+// Reads the parts of TOML our config files use so people who aren't
+// programmers can edit them. Supported :
+//   # comments
+//   key = "text", key = 12, key = 8.25, key = true, key = ["a", "b"]
+//   [table] and [[array of tables]] headings
+// Every value remembers its line so mistakes can be pointed out
+
+use super::date::{self, Date};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    // Used in error messages like "expected text but found a number"
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "text",
+            Value::Integer(_) | Value::Float(_) => "a number",
+            Value::Bool(_) => "true or false",
+            Value::Array(_) => "a list",
+        }
+    }
+}
+
+// A problem in a config file and the line it is on (starting at 1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(line: usize, message: &str) -> ConfigError {
+        ConfigError {
+            line,
+            message: String::from(message),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} : {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// A group of key = value lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+    // The line of the heading, 0 for the top of the file
+    pub line: usize,
+    entries: Vec<(String, Value, usize)>,
+}
+
+impl Table {
+    pub fn keys(&self) -> Vec<&str> {
+        self.entries.iter().map(|(k, _, _)| k.as_str()).collect()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _, _)| k == key)
+    }
+
+    // The value and the line it was written on
+    pub fn get(&self, key: &str) -> Option<(&Value, usize)> {
+        self.entries.iter().find(|(k, _, _)| k == key).map(|(_, v, line)| (v, *line))
+    }
+
+    fn wrong_type(&self, key: &str, expected: &str, found: &Value, line: usize) -> ConfigError {
+        ConfigError::new(line, &format!("{} should be {} but is {}",
+            key, expected, found.type_name()))
+    }
+
+    // Helpers that read a value of one type. Ok(None) means the key
+    // isn't there. A value of the wrong type is an error
+    pub fn string(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some((Value::String(s), _)) => Ok(Some(s.clone())),
+            Some((v, line)) => Err(self.wrong_type(key, "text", v, line)),
+        }
+    }
+
+    pub fn integer(&self, key: &str) -> Result<Option<i64>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some((Value::Integer(n), _)) => Ok(Some(*n)),
+            Some((v, line)) => Err(self.wrong_type(key, "a whole number", v, line)),
+        }
+    }
+
+    // Whole numbers are fine where a decimal is expected
+    pub fn number(&self, key: &str) -> Result<Option<f64>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some((Value::Integer(n), _)) => Ok(Some(*n as f64)),
+            Some((Value::Float(n), _)) => Ok(Some(*n)),
+            Some((v, line)) => Err(self.wrong_type(key, "a number", v, line)),
+        }
+    }
+
+    pub fn boolean(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some((Value::Bool(b), _)) => Ok(Some(*b)),
+            Some((v, line)) => Err(self.wrong_type(key, "true or false", v, line)),
+        }
+    }
+
+    pub fn strings(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some((Value::Array(items), line)) => items.iter().map(|item| match item {
+                Value::String(s) => Ok(s.clone()),
+                v => Err(self.wrong_type(key, "a list of text", v, line)),
+            }).collect::<Result<Vec<String>, ConfigError>>().map(Some),
+            Some((v, line)) => Err(self.wrong_type(key, "a list of text", v, line)),
+        }
+    }
+
+    // A dollar amount like 12.50 turned into cents
+    pub fn money(&self, key: &str) -> Result<Option<u32>, ConfigError> {
+        match self.number(key)? {
+            None => Ok(None),
+            Some(n) if (0.0..1_000_000.0).contains(&n) => Ok(Some((n * 100.0).round() as u32)),
+            Some(_) => Err(ConfigError::new(self.line_of(key),
+                &format!("{} should be a price like 12.50", key))),
+        }
+    }
+
+    // A percent like 8.25 turned into basis points (825)
+    pub fn percent(&self, key: &str) -> Result<Option<u32>, ConfigError> {
+        match self.number(key)? {
+            None => Ok(None),
+            Some(n) if (0.0..=100.0).contains(&n) => Ok(Some((n * 100.0).round() as u32)),
+            Some(_) => Err(ConfigError::new(self.line_of(key),
+                &format!("{} should be a percent from 0 to 100", key))),
+        }
+    }
+
+    // A time of day like "11:30" as minutes since midnight
+    pub fn time_of_day(&self, key: &str) -> Result<Option<u32>, ConfigError> {
+        match self.string(key)? {
+            None => Ok(None),
+            Some(text) => date::parse_time_of_day(&text).map(Some).ok_or_else(|| {
+                ConfigError::new(self.line_of(key),
+                    &format!("{} should be a time like \"14:30\"", key))
+            }),
+        }
+    }
+
+    // A date like "2024-12-31"
+    pub fn date(&self, key: &str) -> Result<Option<Date>, ConfigError> {
+        match self.string(key)? {
+            None => Ok(None),
+            Some(text) => Date::parse(&text).map(Some).ok_or_else(|| {
+                ConfigError::new(self.line_of(key),
+                    &format!("{} should be a date like \"2024-12-31\"", key))
+            }),
+        }
+    }
+
+    // The line a key is on or the table heading if it is missing
+    pub fn line_of(&self, key: &str) -> usize {
+        self.get(key).map_or(self.line, |(_, line)| line)
+    }
+
+    // A key that must be there
+    pub fn required<T>(&self, key: &str, value: Result<Option<T>, ConfigError>)
+        -> Result<T, ConfigError> {
+        value?.ok_or_else(|| ConfigError::new(self.line, &format!("{} is missing", key)))
+    }
+
+    // Complain about keys we don't know, usually a spelling mistake
+    pub fn only_keys(&self, allowed: &[&str]) -> Result<(), ConfigError> {
+        for (key, _, line) in &self.entries {
+            if !allowed.contains(&key.as_str()) {
+                return Err(ConfigError::new(*line, &format!(
+                    "unknown setting {} (expected one of {})", key, allowed.join(", "))));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A whole file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Document {
+    // Keys before the first heading
+    pub root: Table,
+    // [name] tables in the order they appear
+    tables: Vec<(String, Table)>,
+    // [[name]] tables, each heading adds another one
+    arrays: Vec<(String, Vec<Table>)>,
+}
+
+impl Document {
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    // Every [[name]] table, empty if there are none
+    pub fn array(&self, name: &str) -> &[Table] {
+        self.arrays.iter().find(|(n, _)| n == name).map_or(&[], |(_, t)| t.as_slice())
+    }
+
+    // Every heading name used in the file
+    pub fn headings(&self) -> Vec<(&str, usize)> {
+        let mut names: Vec<(&str, usize)> =
+            self.tables.iter().map(|(n, t)| (n.as_str(), t.line)).collect();
+        for (name, tables) in &self.arrays {
+            for table in tables {
+                names.push((name.as_str(), table.line));
+            }
+        }
+        names.sort_by_key(|(_, line)| *line);
+        names
+    }
+
+    pub fn parse(text: &str) -> Result<Document, ConfigError> {
+        let mut doc = Document::default();
+
+        // Where key = value lines are going right now
+        enum Target {
+            Root,
+            Table(usize),
+            Array(usize),
+        }
+        let mut target = Target::Root;
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("[[") {
+                let name = name.strip_suffix("]]")
+                    .ok_or_else(|| ConfigError::new(line_no, "heading is missing ]]"))?;
+                let name = heading_name(name, line_no)?;
+                let table = Table { line: line_no, entries: Vec::new() };
+                let index = match doc.arrays.iter().position(|(n, _)| *n == name) {
+                    Some(index) => index,
+                    None => {
+                        doc.arrays.push((name, Vec::new()));
+                        doc.arrays.len() - 1
+                    }
+                };
+                doc.arrays[index].1.push(table);
+                target = Target::Array(index);
+            } else if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']')
+                    .ok_or_else(|| ConfigError::new(line_no, "heading is missing ]"))?;
+                let name = heading_name(name, line_no)?;
+                if doc.tables.iter().any(|(n, _)| *n == name) {
+                    return Err(ConfigError::new(line_no, &format!("[{}] appears twice", name)));
+                }
+                doc.tables.push((name, Table { line: line_no, entries: Vec::new() }));
+                target = Target::Table(doc.tables.len() - 1);
+            } else {
+                let (key, value) = line.split_once('=').ok_or_else(|| {
+                    ConfigError::new(line_no, "expected key = value or a [heading]")
+                })?;
+                let key = key.trim().trim_matches('"').to_string();
+                if key.is_empty() {
+                    return Err(ConfigError::new(line_no, "the key before = is missing"));
+                }
+                let value = parse_value(value.trim(), line_no)?;
+
+                let table = match target {
+                    Target::Root => &mut doc.root,
+                    Target::Table(i) => &mut doc.tables[i].1,
+                    Target::Array(i) => doc.arrays[i].1.last_mut().unwrap(),
+                };
+                if table.contains(&key) {
+                    return Err(ConfigError::new(line_no, &format!("{} is set twice", key)));
+                }
+                table.entries.push((key, value, line_no));
+            }
+        }
+        Ok(doc)
+    }
+}
+
+fn heading_name(name: &str, line: usize) -> Result<String, ConfigError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ConfigError::new(line, "heading has no name"));
+    }
+    Ok(String::from(name))
+}
+
+// Remove a # comment unless the # is inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '#' {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str, line: usize) -> Result<Value, ConfigError> {
+    if text.is_empty() {
+        return Err(ConfigError::new(line, "the value after = is missing"));
+    }
+    if let Some(rest) = text.strip_prefix('"') {
+        let (s, after) = parse_string(rest, line)?;
+        if !after.trim().is_empty() {
+            return Err(ConfigError::new(line, "unexpected text after the closing quote"));
+        }
+        return Ok(Value::String(s));
+    }
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner.strip_suffix(']')
+            .ok_or_else(|| ConfigError::new(line, "list is missing its closing ]"))?;
+        return split_list(inner, line)?.iter()
+            .map(|item| parse_value(item, line))
+            .collect::<Result<Vec<Value>, ConfigError>>()
+            .map(Value::Array);
+    }
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    // Underscores can make big numbers easier to read like 5_000
+    let number = text.replace('_', "");
+    if let Ok(n) = number.parse::<i64>() {
+        return Ok(Value::Integer(n));
+    }
+    if let Ok(n) = number.parse::<f64>() {
+        if n.is_finite() {
+            return Ok(Value::Float(n));
+        }
+    }
+    Err(ConfigError::new(line, &format!(
+        "{} isn't a value, text needs \"quotes\" around it", text)))
+}
+
+// Reads up to the closing quote. Returns the text and whatever follows
+fn parse_string(text: &str, line: usize) -> Result<(String, &str), ConfigError> {
+    let mut s = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((s, &text[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => s.push('\n'),
+                Some((_, 't')) => s.push('\t'),
+                Some((_, '"')) => s.push('"'),
+                Some((_, '\\')) => s.push('\\'),
+                _ => return Err(ConfigError::new(line, "unknown escape in text")),
+            },
+            c => s.push(c),
+        }
+    }
+    Err(ConfigError::new(line, "text is missing its closing quote"))
+}
+
+// Split the inside of a list on commas that aren't inside quotes
+fn split_list(inner: &str, line: usize) -> Result<Vec<String>, ConfigError> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in inner.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            items.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+    }
+    if in_string {
+        return Err(ConfigError::new(line, "text in the list is missing its closing quote"));
+    }
+    items.push(current.trim().to_string());
+
+    // A trailing comma leaves an empty item at the end
+    if items.last().is_some_and(|i| i.is_empty()) {
+        items.pop();
+    }
+    if items.iter().any(|i| i.is_empty()) {
+        return Err(ConfigError::new(line, "list has an empty item"));
+    }
+    Ok(items)
+}
+
+// Reading a config file can fail because the file can't be read or
+// because of what is written in it
+#[derive(Debug)]
+pub enum LoadError {
+    Io(String, io::Error),
+    Config(String, ConfigError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "Couldn't read {} : {}", path, e),
+            LoadError::Config(path, e) => write!(f, "{} {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl LoadError {
+    // True when the file simply isn't there
+    pub fn is_missing(&self) -> bool {
+        matches!(self, LoadError::Io(_, e) if e.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+// Read a file and check it with a function that turns the document
+// into whatever the file describes
+pub fn load<T, F>(path: &Path, read: F) -> Result<T, LoadError>
+where
+    F: FnOnce(&Document) -> Result<T, ConfigError>,
+{
+    let name = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|e| LoadError::Io(name.clone(), e))?;
+    Document::parse(&text)
+        .and_then(|doc| read(&doc))
+        .map_err(|e| LoadError::Config(name, e))
+}